@@ -1,24 +1,22 @@
-use std::collections::HashSet;
-
 use aoc_runner_derive::{aoc, aoc_generator};
 
+use super::grid::Grid;
+
 pub struct Map {
-    trees: HashSet<(usize, usize)>,
-    width: usize,
-    height: usize,
+    trees: Grid<bool>,
 }
 
 impl Map {
     fn count_trees_on_slope(&self, right: usize, down: usize) -> usize {
-        let width = self.width;
+        let width = self.trees.width();
         let trees = &self.trees;
 
-        (0..=self.height)
+        (0..trees.height())
             .step_by(down)
             .enumerate()
             .filter(|&(idx, y)| {
                 let x = (idx * right) % width;
-                trees.contains(&(x, y))
+                trees[(y, x)]
             })
             .count()
     }
@@ -27,28 +25,9 @@ impl Map {
 const TREE: char = '#';
 
 #[aoc_generator(day3)]
-pub fn generate(inp: &str) -> Map {
-    let height = inp.lines().count();
-    let width = inp.lines().next().map_or(0, |l| l.chars().count());
-
-    let trees = inp
-        .lines()
-        .enumerate()
-        .fold(HashSet::new(), |acc, (y, line)| {
-            line.chars().enumerate().fold(acc, |mut acc, (idx, chr)| {
-                if chr == TREE {
-                    acc.insert((idx, y));
-                }
-
-                acc
-            })
-        });
-
-    Map {
-        trees,
-        width,
-        height,
-    }
+pub fn generate(inp: &str) -> Option<Map> {
+    let trees = Grid::parse(inp, |chr| Some(chr == TREE))?;
+    Some(Map { trees })
 }
 
 #[aoc(day3, part1)]
@@ -84,13 +63,17 @@ mod test {
 
     #[test]
     fn test_part1() {
-        let data = generate(INP);
+        let Some(data) = generate(INP) else {
+            panic!("Could not parse test input")
+        };
         assert_eq!(7, part1(&data));
     }
 
     #[test]
     fn test_part2() {
-        let data = generate(INP);
+        let Some(data) = generate(INP) else {
+            panic!("Could not parse test input")
+        };
         assert_eq!(336, part2(&data));
     }
 }
@@ -102,7 +102,7 @@ fn validate_height(height: &str) -> bool {
     height
         .parse::<HeightUnit>()
         .ok()
-        .map_or(false, |it| match it {
+        .is_some_and(|it| match it {
             HeightUnit::Centimeter(num) => validate_range(150, 193, num),
             HeightUnit::Inch(num) => validate_range(59, 76, num),
         })
@@ -68,7 +68,7 @@ fn can_hold_shiny_gold(
 
         let res = all_bags
             .get(k)
-            .map_or(false, |it| can_hold_shiny_gold(it, all_bags, bag_cache));
+            .is_some_and(|it| can_hold_shiny_gold(it, all_bags, bag_cache));
 
         bag_cache.insert(k.to_string(), res);
 
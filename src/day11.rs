@@ -1,204 +1,365 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use aoc_runner_derive::{aoc, aoc_generator};
-use itertools::iproduct;
+use itertools::{iproduct, Itertools};
+use parse_display::Display as PDisplay;
 
+use super::grid::{Grid, Pos, ADJACENT};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Neighborhood {
+    Adjacent,
+    LineOfSight,
+    Radius(usize),
+}
+
+#[derive(Copy, Clone, Debug)]
 pub struct SimulationContext {
-    single_step: bool,
+    neighborhood: Neighborhood,
+    birth_limit: usize,
     crowd_limit: usize,
+    max_iterations: Option<usize>,
 }
 
 impl SimulationContext {
-    pub const fn new(single_step: bool, crowd_limit: usize) -> Self {
+    pub const fn new(neighborhood: Neighborhood, crowd_limit: usize) -> Self {
         Self {
-            single_step,
+            neighborhood,
+            birth_limit: 0,
             crowd_limit,
+            max_iterations: None,
+        }
+    }
+
+    pub const fn with_birth_limit(self, birth_limit: usize) -> Self {
+        Self {
+            birth_limit,
+            ..self
+        }
+    }
+
+    pub const fn with_max_iterations(self, max_iterations: usize) -> Self {
+        Self {
+            max_iterations: Some(max_iterations),
+            ..self
         }
     }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SimulationOutcome {
+    Stable { generation: usize, occupied: usize },
+    Cycle { start: usize, period: usize },
+    IterationLimit { generations: usize, occupied: usize },
+}
+
+#[derive(PDisplay, Copy, Clone, PartialEq, Eq, Debug)]
 pub enum MapTile {
+    #[display(".")]
     Floor,
+
+    #[display("L")]
     Empty,
+
+    #[display("#")]
     Occupied,
 }
 
-type MapElements = HashMap<(usize, usize), MapTile>;
-
-#[derive(Clone)]
+#[derive(PDisplay, Clone)]
+#[display("{grid}")]
 pub struct MapData {
-    width: usize,
-    height: usize,
-    elements: MapElements,
+    grid: Grid<MapTile>,
 }
 
 impl MapData {
-    #[allow(clippy::cast_possible_wrap)]
-    const fn is_valid_pos(&self, row: i64, col: i64) -> bool {
-        row >= 0 && col >= 0 && row < self.height as i64 && col < self.width as i64
-    }
-
-    fn get_next_state(
-        &self,
-        row: usize,
-        col: usize,
-        context: &SimulationContext,
-    ) -> Option<MapTile> {
-        let cur_seat = self.elements.get(&(row, col))?;
-        if *cur_seat == MapTile::Floor {
-            return Some(MapTile::Floor);
-        }
-
-        #[allow(clippy::cast_possible_wrap)]
-        let num_occupied_adjacent =
-            self.count_visible_occupied_seats(row as i64, col as i64, context.single_step);
-
-        Some(match *cur_seat {
-            MapTile::Empty if num_occupied_adjacent == 0 => MapTile::Occupied,
-            MapTile::Occupied if num_occupied_adjacent >= context.crowd_limit => MapTile::Empty,
-            seat => seat,
-        })
-    }
-
-    fn count_visible_occupied_seats(&self, row: i64, col: i64, single_step: bool) -> usize {
-        const DIRECTIONS: &[(i64, i64); 8] = &[
-            (-1, -1),
-            (-1, 0),
-            (-1, 1),
-            (0, -1),
-            (0, 1),
-            (1, -1),
-            (1, 0),
-            (1, 1),
-        ];
-
-        DIRECTIONS
+    fn visible_seats(&self, pos: Pos, neighborhood: Neighborhood) -> Vec<Pos> {
+        match neighborhood {
+            Neighborhood::Adjacent => self.first_seats_in_sight(pos, true),
+            Neighborhood::LineOfSight => self.first_seats_in_sight(pos, false),
+            Neighborhood::Radius(radius) => self.seats_in_radius(pos, radius),
+        }
+    }
+
+    fn first_seats_in_sight(&self, pos: Pos, single_step: bool) -> Vec<Pos> {
+        ADJACENT
             .iter()
-            .map(|(rd, cd)| {
-                self.count_occupied_seats(row, col, *rd, *cd, single_step)
-                    .unwrap_or(0)
-            })
-            .sum()
-    }
-
-    fn count_occupied_seats(
-        &self,
-        row: i64,
-        col: i64,
-        row_dir: i64,
-        col_dir: i64,
-        single_step: bool,
-    ) -> Option<usize> {
-        let mut cur_row = row + row_dir;
-        let mut cur_col = col + col_dir;
-
-        loop {
-            if !self.is_valid_pos(cur_row, cur_col) || (cur_row == row && cur_col == col) {
-                return None;
-            }
+            .filter_map(|dir| self.first_seat_in_direction(pos, *dir, single_step))
+            .collect()
+    }
 
-            let cur_seat = self.elements.get(&(cur_row as usize, cur_col as usize))?;
+    fn seats_in_radius(&self, (row, col): Pos, radius: usize) -> Vec<Pos> {
+        let rows =
+            row.saturating_sub(radius)..=row.saturating_add(radius).min(self.grid.height() - 1);
+        let cols =
+            col.saturating_sub(radius)..=col.saturating_add(radius).min(self.grid.width() - 1);
 
-            match *cur_seat {
-                MapTile::Occupied => return Some(1),
-                MapTile::Empty => return None,
-                MapTile::Floor => {}
-            };
+        iproduct!(rows, cols)
+            .filter(|it| *it != (row, col))
+            .filter(|it| self.grid[*it] != MapTile::Floor)
+            .collect()
+    }
+
+    fn first_seat_in_direction(&self, pos: Pos, dir: (i64, i64), single_step: bool) -> Option<Pos> {
+        let mut cur_pos = pos;
+
+        while let Some(next_pos) = self.grid.offset(cur_pos, dir) {
+            if self.grid[next_pos] != MapTile::Floor {
+                return Some(next_pos);
+            }
 
             if single_step {
                 return None;
             }
 
-            cur_row += row_dir;
-            cur_col += col_dir;
+            cur_pos = next_pos;
         }
+
+        None
     }
 
     pub fn count_occupied(&self) -> usize {
-        self.elements
-            .iter()
-            .filter(|(_, seat)| **seat == MapTile::Occupied)
+        self.grid
+            .values()
+            .filter(|seat| **seat == MapTile::Occupied)
             .count()
     }
 
-    pub const fn new(width: usize, height: usize, elements: MapElements) -> Self {
+    pub const fn new(grid: Grid<MapTile>) -> Self {
+        Self { grid }
+    }
+}
+
+pub struct Simulation {
+    context: SimulationContext,
+    map: MapData,
+    seats: Vec<Pos>,
+    visible: Vec<Vec<usize>>,
+    watchers: Vec<Vec<usize>>,
+    occupied: Vec<bool>,
+    dirty: Vec<usize>,
+}
+
+impl Simulation {
+    pub fn new(map: &MapData, context: SimulationContext) -> Self {
+        let seats = map
+            .grid
+            .iter()
+            .filter(|(_, tile)| **tile != MapTile::Floor)
+            .map(|(pos, _)| pos)
+            .collect_vec();
+
+        let mut seat_index = map.grid.map(|_| None);
+        for (idx, pos) in seats.iter().enumerate() {
+            seat_index[*pos] = Some(idx);
+        }
+
+        let visible = seats
+            .iter()
+            .map(|pos| {
+                map.visible_seats(*pos, context.neighborhood)
+                    .into_iter()
+                    .filter_map(|it| seat_index[it])
+                    .collect_vec()
+            })
+            .collect_vec();
+
+        let mut watchers = vec![Vec::new(); seats.len()];
+        for (idx, seen) in visible.iter().enumerate() {
+            for other in seen {
+                watchers[*other].push(idx);
+            }
+        }
+
+        let occupied = seats
+            .iter()
+            .map(|pos| map.grid[*pos] == MapTile::Occupied)
+            .collect_vec();
+
         Self {
-            width,
-            height,
-            elements,
+            context,
+            map: map.clone(),
+            dirty: (0..seats.len()).collect(),
+            seats,
+            visible,
+            watchers,
+            occupied,
         }
     }
-}
 
-fn run_iteration(prev_map: &MapData, context: &SimulationContext) -> Option<(MapElements, bool)> {
-    let mut next_map_elements = MapElements::new();
-    let mut has_changes = false;
+    fn next_state(&self, seat: usize) -> bool {
+        let num_occupied = self.visible[seat]
+            .iter()
+            .filter(|it| self.occupied[**it])
+            .count();
 
-    for (row, col) in iproduct!(0..prev_map.height, 0..prev_map.width) {
-        let cur_state = prev_map.elements.get(&(row, col))?;
-        let next_state = prev_map.get_next_state(row, col, context)?;
-        next_map_elements.insert((row, col), next_state);
+        if self.occupied[seat] {
+            num_occupied < self.context.crowd_limit
+        } else {
+            num_occupied <= self.context.birth_limit
+        }
+    }
+
+    fn is_stable(&self) -> bool {
+        self.dirty
+            .iter()
+            .all(|seat| self.next_state(*seat) == self.occupied[*seat])
+    }
+
+    pub fn step(&mut self) -> bool {
+        let changed = self
+            .dirty
+            .iter()
+            .copied()
+            .filter(|seat| self.next_state(*seat) != self.occupied[*seat])
+            .collect_vec();
+
+        let mut is_dirty = vec![false; self.seats.len()];
+        for seat in &changed {
+            let now_occupied = !self.occupied[*seat];
+            self.occupied[*seat] = now_occupied;
+            self.map.grid[self.seats[*seat]] = if now_occupied {
+                MapTile::Occupied
+            } else {
+                MapTile::Empty
+            };
 
-        if next_state != *cur_state {
-            has_changes = true;
+            is_dirty[*seat] = true;
+            for watcher in &self.watchers[*seat] {
+                is_dirty[*watcher] = true;
+            }
         }
+
+        self.dirty = (0..self.seats.len()).filter(|it| is_dirty[*it]).collect();
+
+        !changed.is_empty()
+    }
+
+    pub const fn map(&self) -> &MapData {
+        &self.map
     }
 
-    Some((next_map_elements, has_changes))
+    pub fn count_occupied(&self) -> usize {
+        self.occupied.iter().filter(|it| **it).count()
+    }
 }
 
-pub fn run_simulation_until_stable(map: &MapData, context: &SimulationContext) -> Option<usize> {
-    let mut prev_map = map.clone();
-    loop {
-        let (next_map_elems, has_changes) = run_iteration(&prev_map, context)?;
+// Yields every generation until the map is stable, a state repeats or the
+// iteration limit is reached
+pub struct Generations {
+    simulation: Simulation,
+    generation: Option<usize>,
+    seen: HashSet<Vec<bool>>,
+    done: bool,
+}
+
+impl Iterator for Generations {
+    type Item = MapData;
 
-        if !has_changes {
-            break;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
         }
 
-        prev_map.elements = next_map_elems;
+        let generation = match self.generation {
+            None => 0,
+            Some(prev) => {
+                let max_iterations = self.simulation.context.max_iterations;
+                if max_iterations.is_some_and(|it| prev >= it) || !self.simulation.step() {
+                    self.done = true;
+                    return None;
+                }
+
+                prev + 1
+            }
+        };
+        self.generation = Some(generation);
+
+        if !self.seen.insert(self.simulation.occupied.clone()) {
+            self.done = true;
+            return None;
+        }
+
+        Some(self.simulation.map().clone())
     }
+}
 
-    Some(prev_map.count_occupied())
+pub fn generations(map: &MapData, context: &SimulationContext) -> Generations {
+    Generations {
+        simulation: Simulation::new(map, *context),
+        generation: None,
+        seen: HashSet::new(),
+        done: false,
+    }
 }
 
-#[aoc_generator(day11)]
-pub fn generate(inp: &str) -> MapData {
-    let height = inp.lines().count();
+pub fn export_text_frames(map: &MapData, context: &SimulationContext) -> String {
+    generations(map, context)
+        .enumerate()
+        .map(|(idx, it)| format!("Generation {idx}:\n{it}\n"))
+        .join("\n")
+}
 
-    let mut width = 0;
+pub fn run_simulation(map: &MapData, context: &SimulationContext) -> SimulationOutcome {
+    let mut simulation = Simulation::new(map, *context);
+    let mut seen = HashMap::new();
 
-    let elements = inp
-        .lines()
-        .enumerate()
-        .fold(HashMap::new(), |acc, (row, it)| {
-            width = it.len();
-
-            it.chars().enumerate().fold(acc, |mut acc, (col, chr)| {
-                match chr {
-                    '#' => acc.insert((row, col), MapTile::Occupied),
-                    'L' => acc.insert((row, col), MapTile::Empty),
-                    '.' => acc.insert((row, col), MapTile::Floor),
-                    _ => panic!("Invalid char in input"),
-                };
-
-                acc
-            })
-        });
+    let mut generation = 0;
 
-    MapData::new(width, height, elements)
+    loop {
+        if let Some(start) = seen.insert(simulation.occupied.clone(), generation) {
+            return SimulationOutcome::Cycle {
+                start,
+                period: generation - start,
+            };
+        }
+
+        if simulation.is_stable() {
+            return SimulationOutcome::Stable {
+                generation,
+                occupied: simulation.count_occupied(),
+            };
+        }
+
+        if context.max_iterations.is_some_and(|it| generation >= it) {
+            return SimulationOutcome::IterationLimit {
+                generations: generation,
+                occupied: simulation.count_occupied(),
+            };
+        }
+
+        simulation.step();
+        generation += 1;
+    }
+}
+
+pub fn run_simulation_until_stable(map: &MapData, context: &SimulationContext) -> Option<usize> {
+    match run_simulation(map, context) {
+        SimulationOutcome::Stable { occupied, .. } => Some(occupied),
+        _ => None,
+    }
+}
+
+#[aoc_generator(day11)]
+pub fn generate(inp: &str) -> Option<MapData> {
+    let grid = Grid::parse(inp, |chr| match chr {
+        '#' => Some(MapTile::Occupied),
+        'L' => Some(MapTile::Empty),
+        '.' => Some(MapTile::Floor),
+        _ => None,
+    })?;
+
+    Some(MapData::new(grid))
 }
 
 #[aoc(day11, part1)]
 pub fn part1(map: &MapData) -> Option<usize> {
-    let context = SimulationContext::new(true, 4);
+    let context = SimulationContext::new(Neighborhood::Adjacent, 4);
     run_simulation_until_stable(map, &context)
 }
 
 #[aoc(day11, part2)]
 pub fn part2(map: &MapData) -> Option<usize> {
-    let context = SimulationContext::new(false, 5);
+    let context = SimulationContext::new(Neighborhood::LineOfSight, 5);
     run_simulation_until_stable(map, &context)
 }
 
@@ -206,9 +367,7 @@ pub fn part2(map: &MapData) -> Option<usize> {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_sample_part1() {
-        let inp = "L.LL.LL.LL
+    const INP: &str = "L.LL.LL.LL
 LLLLLLL.LL
 L.L.L..L..
 LLLL.LL.LL
@@ -218,23 +377,166 @@ L.LLLLL.LL
 LLLLLLLLLL
 L.LLLLLL.L
 L.LLLLL.LL";
-        let data = generate(inp);
+
+    #[test]
+    fn test_sample_part1() {
+        let Some(data) = generate(INP) else {
+            panic!("Could not parse test input")
+        };
         assert_eq!(Some(37), part1(&data));
     }
 
     #[test]
     fn test_sample_part2() {
-        let inp = "L.LL.LL.LL
-LLLLLLL.LL
-L.L.L..L..
-LLLL.LL.LL
-L.LL.LL.LL
-L.LLLLL.LL
-..L.L.....
-LLLLLLLLLL
-L.LLLLLL.L
-L.LLLLL.LL";
-        let data = generate(inp);
+        let Some(data) = generate(INP) else {
+            panic!("Could not parse test input")
+        };
         assert_eq!(Some(26), part2(&data));
     }
+
+    #[test]
+    fn test_custom_rules() {
+        let Some(data) = generate(INP) else {
+            panic!("Could not parse test input")
+        };
+
+        let context = SimulationContext::new(Neighborhood::Radius(1), 4);
+        assert_eq!(Some(37), run_simulation_until_stable(&data, &context));
+
+        let context = SimulationContext::new(Neighborhood::Adjacent, 9).with_birth_limit(8);
+        assert_eq!(Some(71), run_simulation_until_stable(&data, &context));
+
+        // Any radius past the grid size sees the whole map
+        let whole_map = SimulationContext::new(Neighborhood::Radius(10), 40);
+        let unbounded = SimulationContext::new(Neighborhood::Radius(usize::MAX), 40);
+        assert_eq!(
+            run_simulation(&data, &whole_map),
+            run_simulation(&data, &unbounded)
+        );
+    }
+
+    #[test]
+    fn test_simulation_rounds() {
+        let Some(data) = generate(INP) else {
+            panic!("Could not parse test input")
+        };
+
+        let mut simulation =
+            Simulation::new(&data, SimulationContext::new(Neighborhood::Adjacent, 4));
+        assert!(simulation.step());
+        assert_eq!(71, simulation.count_occupied());
+        assert_eq!(71, simulation.map().count_occupied());
+
+        let mut rounds = 1;
+        while simulation.step() {
+            rounds += 1;
+        }
+        assert_eq!(5, rounds);
+        assert_eq!(37, simulation.map().count_occupied());
+    }
+
+    #[test]
+    fn test_generations() {
+        let Some(data) = generate(INP) else {
+            panic!("Could not parse test input")
+        };
+
+        let context = SimulationContext::new(Neighborhood::LineOfSight, 5);
+        let frames = generations(&data, &context).collect_vec();
+        assert_eq!(7, frames.len());
+        assert_eq!(INP, frames[0].to_string());
+        assert_eq!(INP.replace('L', "#"), frames[1].to_string());
+        assert_eq!(26, frames[6].count_occupied());
+
+        let exported = export_text_frames(&data, &context);
+        assert!(exported.starts_with("Generation 0:\nL.LL.LL.LL\n"));
+        assert!(exported.contains("Generation 6:\n"));
+
+        let limited = context.with_max_iterations(2);
+        assert_eq!(3, generations(&data, &limited).count());
+
+        let Some(single_seat) = generate(".L.") else {
+            panic!("Could not parse test input")
+        };
+        let context = SimulationContext::new(Neighborhood::Adjacent, 0).with_max_iterations(5);
+        assert_eq!(
+            "Generation 0:\n.L.\n\nGeneration 1:\n.#.\n",
+            export_text_frames(&single_seat, &context)
+        );
+        let context = SimulationContext::new(Neighborhood::Adjacent, 0);
+        assert_eq!(2, generations(&single_seat, &context).count());
+    }
+
+    #[test]
+    fn test_simulation_outcomes() {
+        let Some(data) = generate(INP) else {
+            panic!("Could not parse test input")
+        };
+
+        let context = SimulationContext::new(Neighborhood::Adjacent, 4);
+        assert_eq!(
+            SimulationOutcome::Stable {
+                generation: 5,
+                occupied: 37
+            },
+            run_simulation(&data, &context)
+        );
+        assert_eq!(
+            SimulationOutcome::IterationLimit {
+                generations: 2,
+                occupied: 20
+            },
+            run_simulation(&data, &context.with_max_iterations(2))
+        );
+        assert_eq!(
+            SimulationOutcome::Stable {
+                generation: 5,
+                occupied: 37
+            },
+            run_simulation(&data, &context.with_max_iterations(5))
+        );
+
+        let Some(single_seat) = generate(".L.") else {
+            panic!("Could not parse test input")
+        };
+        let context = SimulationContext::new(Neighborhood::Adjacent, 0);
+        assert_eq!(
+            SimulationOutcome::Cycle {
+                start: 0,
+                period: 2
+            },
+            run_simulation(&single_seat, &context)
+        );
+        assert_eq!(None, run_simulation_until_stable(&single_seat, &context));
+    }
+
+    #[test]
+    fn test_limit_matches_generations() {
+        let Some(data) = generate(INP) else {
+            panic!("Could not parse test input")
+        };
+
+        let context = SimulationContext::new(Neighborhood::LineOfSight, 5);
+        for limit in 0..8 {
+            let limited = context.with_max_iterations(limit);
+            let Some(last) = generations(&data, &limited).last() else {
+                panic!("No generations")
+            };
+            let last_generation = generations(&data, &limited).count() - 1;
+
+            let (generation, occupied) = match run_simulation(&data, &limited) {
+                SimulationOutcome::Stable {
+                    generation,
+                    occupied,
+                } => (generation, occupied),
+                SimulationOutcome::IterationLimit {
+                    generations,
+                    occupied,
+                } => (generations, occupied),
+                SimulationOutcome::Cycle { .. } => panic!("Unexpected cycle"),
+            };
+            assert_eq!(last_generation, generation);
+            assert_eq!(last.count_occupied(), occupied);
+        }
+    }
 }
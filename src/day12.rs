@@ -1,6 +1,8 @@
 use aoc_runner_derive::{aoc, aoc_generator};
+use itertools::Itertools;
+use num_complex::Complex;
 
-use super::ship::{NavigationAction, Ship, Simple, WithWaypoint};
+use super::ship::{NavigationAction, NavigationError, Ship, Simple, WithWaypoint};
 
 #[aoc_generator(day12)]
 pub fn generate(inp: &str) -> Vec<NavigationAction> {
@@ -9,27 +11,151 @@ pub fn generate(inp: &str) -> Vec<NavigationAction> {
         .collect()
 }
 
-fn run_actions<T>(actions: &[NavigationAction], ship: &mut T) -> i64
+pub fn run_actions<T>(actions: &[NavigationAction], ship: &mut T) -> Result<i64, NavigationError>
 where
     T: Ship,
 {
-    actions.iter().for_each(|it| ship.perform_action(it));
-    ship.get_distance()
+    for action in actions {
+        ship.perform_action(action)?;
+    }
+
+    Ok(ship.get_distance())
+}
+
+// One action per axis, every action moves the ship along a single axis
+fn axis_moves(delta: Complex<i64>) -> Vec<NavigationAction> {
+    let east_west = match delta.re {
+        0 => None,
+        re if re > 0 => Some(NavigationAction::East(re)),
+        re => Some(NavigationAction::West(-re)),
+    };
+    let north_south = match delta.im {
+        0 => None,
+        im if im > 0 => Some(NavigationAction::North(im)),
+        im => Some(NavigationAction::South(-im)),
+    };
+
+    east_west.into_iter().chain(north_south).collect()
+}
+
+fn gcd(lhs: i64, rhs: i64) -> i64 {
+    if rhs == 0 {
+        lhs.abs()
+    } else {
+        gcd(rhs, lhs % rhs)
+    }
+}
+
+// Trial division up to 2^20 is still quick, larger common factors skip the search
+const MAX_DIVISOR_SEARCH: i64 = 1 << 40;
+
+fn divisors(n: i64) -> Vec<i64> {
+    let n = n.abs();
+    let small = (1..)
+        .take_while(|it| *it <= n / it)
+        .filter(|it| n % it == 0);
+
+    small
+        .flat_map(|it| vec![it, n / it])
+        .sorted_unstable()
+        .dedup()
+        .collect()
+}
+
+// At most one action that turns `waypoint` into `target`, both relative to the ship
+fn adjust_waypoint(waypoint: Complex<i64>, target: Complex<i64>) -> Option<Vec<NavigationAction>> {
+    let rotations = [
+        (Complex::i(), NavigationAction::Left(90)),
+        (-Complex::<i64>::i(), NavigationAction::Right(90)),
+        (Complex::new(-1, 0), NavigationAction::Right(180)),
+    ];
+
+    if let Some((_, turn)) = rotations.iter().find(|(rot, _)| waypoint * rot == target) {
+        return Some(vec![*turn]);
+    }
+
+    let moves = axis_moves(target - waypoint);
+    (moves.len() <= 1).then_some(moves)
+}
+
+// Only forward moves the ship, so the shortest plan is a single forward move along
+// a suitable waypoint, after at most one adjustment. Failing that, moving the
+// waypoint right onto the target and going forward once always works.
+fn waypoint_route(waypoint: Complex<i64>, delta: Complex<i64>) -> Vec<NavigationAction> {
+    if delta == Complex::new(0, 0) {
+        return Vec::new();
+    }
+
+    let common = gcd(delta.re, delta.im);
+    let steps = if common <= MAX_DIVISOR_SEARCH {
+        divisors(common)
+    } else {
+        Vec::new()
+    };
+
+    let shortest = steps
+        .into_iter()
+        .filter_map(|steps| {
+            let mut actions = adjust_waypoint(waypoint, delta / steps)?;
+            actions.push(NavigationAction::Forward(steps));
+            Some(actions)
+        })
+        .min_by_key(Vec::len);
+
+    shortest.unwrap_or_else(|| {
+        let mut actions = axis_moves(delta - waypoint);
+        actions.push(NavigationAction::Forward(1));
+        actions
+    })
+}
+
+// The route assumes plain heading or waypoint steering. Models that bend it, like
+// a drifting ship or one with too little fuel, get no route at all.
+pub fn plan_route<T: Ship + Clone>(
+    ship: &T,
+    target: Complex<i64>,
+) -> Option<Vec<NavigationAction>> {
+    let delta = target - ship.position();
+
+    let route = match ship.waypoint() {
+        Some(waypoint) => waypoint_route(waypoint - ship.position(), delta),
+        None => axis_moves(delta),
+    };
+
+    verify_route(ship, &route, target)
+        .unwrap_or(false)
+        .then_some(route)
+}
+
+pub fn verify_route<T: Ship + Clone>(
+    ship: &T,
+    actions: &[NavigationAction],
+    target: Complex<i64>,
+) -> Result<bool, NavigationError> {
+    let mut ship = ship.clone();
+    run_actions(actions, &mut ship)?;
+
+    Ok(ship.position() == target)
+}
+
+pub fn format_actions(actions: &[NavigationAction]) -> String {
+    actions.iter().join("\n")
 }
 
 #[aoc(day12, part1)]
-pub fn part1(v: &[NavigationAction]) -> i64 {
+pub fn part1(v: &[NavigationAction]) -> Result<i64, NavigationError> {
     run_actions(v, &mut Simple::new())
 }
 
 #[aoc(day12, part2)]
-pub fn part2(v: &[NavigationAction]) -> i64 {
+pub fn part2(v: &[NavigationAction]) -> Result<i64, NavigationError> {
     run_actions(v, &mut WithWaypoint::new())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ship::{Drifting, FuelLimited};
 
     #[test]
     fn test_sample_part1() {
@@ -41,7 +167,7 @@ mod tests {
             NavigationAction::Forward(11),
         ];
 
-        assert_eq!(25, part1(&inp));
+        assert_eq!(Ok(25), part1(&inp));
     }
 
     #[test]
@@ -54,6 +180,118 @@ mod tests {
             NavigationAction::Forward(11),
         ];
 
-        assert_eq!(286, part2(&inp));
+        assert_eq!(Ok(286), part2(&inp));
+    }
+
+    #[test]
+    fn test_invalid_turn() {
+        let turn = NavigationAction::Left(135);
+        let inp = vec![NavigationAction::Forward(10), turn];
+
+        assert_eq!(Err(NavigationError::UnsupportedTurn(turn)), part1(&inp));
+        assert_eq!(Err(NavigationError::UnsupportedTurn(turn)), part2(&inp));
+    }
+
+    #[test]
+    fn test_composed_models() {
+        let inp = vec![
+            NavigationAction::Forward(10),
+            NavigationAction::North(3),
+            NavigationAction::Forward(7),
+            NavigationAction::Right(90),
+            NavigationAction::Forward(11),
+        ];
+
+        let mut ship = FuelLimited::new(Drifting::new(Simple::new(), Complex::new(0, 0)), 31);
+        assert_eq!(Ok(25), run_actions(&inp, &mut ship));
+
+        ship.reset();
+        assert_eq!(Ok(25), run_actions(&inp, &mut ship));
+
+        let mut ship = FuelLimited::new(WithWaypoint::new(), 200);
+        assert_eq!(
+            Err(NavigationError::OutOfFuel {
+                needed: 98,
+                remaining: 90
+            }),
+            run_actions(&inp, &mut ship)
+        );
+    }
+
+    #[test]
+    fn test_plan_route() {
+        let ship = Simple::new();
+        let Some(route) = plan_route(&ship, Complex::new(17, -8)) else {
+            panic!("No route found")
+        };
+        assert_eq!("E17\nS8", format_actions(&route));
+        assert_eq!(Ok(true), verify_route(&ship, &route, Complex::new(17, -8)));
+        assert_eq!(Some(Vec::new()), plan_route(&ship, Complex::new(0, 0)));
+
+        let ship = WithWaypoint::new();
+        for (target, expected) in &[
+            (Complex::new(0, 0), ""),
+            (Complex::new(100, 10), "F10"),
+            (Complex::new(214, -72), "E204\nS73\nF1"),
+            (Complex::new(170, 68), "N3\nF17"),
+            (Complex::new(-20, 200), "L90\nF20"),
+            (Complex::new(60, -12), "S3\nF6"),
+            (Complex::new(-7, -7), "W17\nS8\nF1"),
+        ] {
+            let Some(route) = plan_route(&ship, *target) else {
+                panic!("No route found")
+            };
+            assert_eq!(Ok(true), verify_route(&ship, &route, *target));
+            assert_eq!(*expected, format_actions(&route));
+        }
+    }
+
+    #[test]
+    fn test_plan_from_current_state() {
+        let inp = vec![
+            NavigationAction::Forward(10),
+            NavigationAction::North(3),
+            NavigationAction::Forward(7),
+            NavigationAction::Right(90),
+            NavigationAction::Forward(11),
+        ];
+
+        let mut ship = WithWaypoint::new();
+        assert_eq!(Ok(286), run_actions(&inp, &mut ship));
+
+        let Some(route) = plan_route(&ship, Complex::new(0, 0)) else {
+            panic!("No route found")
+        };
+        assert_eq!(Ok(true), verify_route(&ship, &route, Complex::new(0, 0)));
+        assert!(route.len() <= 3);
+    }
+
+    #[test]
+    fn test_plan_far_target() {
+        let ship = WithWaypoint::new();
+        let target = Complex::new(4_000_000_000_000_000_000, 0);
+
+        let Some(route) = plan_route(&ship, target) else {
+            panic!("No route found")
+        };
+        assert_eq!(Ok(true), verify_route(&ship, &route, target));
+        assert_eq!(3, route.len());
+    }
+
+    #[test]
+    fn test_unplannable_models() {
+        let target = Complex::new(10, 0);
+
+        let ship = Drifting::new(Simple::new(), Complex::new(0, -1));
+        assert_eq!(None, plan_route(&ship, target));
+
+        let ship = FuelLimited::new(Simple::new(), 5);
+        assert_eq!(None, plan_route(&ship, target));
+
+        let ship = FuelLimited::new(Simple::new(), 10);
+        assert_eq!(
+            Some(vec![NavigationAction::East(10)]),
+            plan_route(&ship, target)
+        );
     }
 }
@@ -1,13 +1,29 @@
+use std::iter::successors;
+
 use aoc_runner_derive::{aoc, aoc_generator};
-use itertools::Itertools;
+use parse_display::Display as PDisplay;
+
+#[derive(PDisplay, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ScheduleError {
+    #[display("The buses never line up")]
+    NoSolution,
+
+    #[display("The alignment period does not fit in 128 bits")]
+    Overflow,
+
+    #[display("There is no bus {0} in the schedule")]
+    UnknownBus(u64),
+}
+
+impl std::error::Error for ScheduleError {}
 
 struct BusData {
-    id: i64,
-    order: i64,
+    id: u64,
+    order: u64,
 }
 
 pub struct BusSchedule {
-    earliest_depart: i64,
+    earliest_depart: u128,
     buses: Vec<BusData>,
 }
 
@@ -16,18 +32,19 @@ pub fn generate(inp: &str) -> Option<BusSchedule> {
     let lines = &mut inp.lines();
 
     let first_line = lines.next()?;
-    let earliest_depart = first_line.parse::<i64>().ok()?;
+    let earliest_depart = first_line.parse::<u128>().ok()?;
 
     let second_line = lines.next()?;
     let buses = second_line
         .split(',')
         .enumerate()
-        .filter_map(|(order, it)| {
-            #[allow(clippy::cast_possible_wrap)]
-            let order = order as i64;
-            it.parse::<i64>().ok().map(|id| BusData { id, order })
+        .filter(|(_, it)| *it != "x")
+        .map(|(order, it)| {
+            let id = it.parse::<u64>().ok().filter(|id| *id > 0)?;
+            let order = order as u64;
+            Some(BusData { id, order })
         })
-        .collect();
+        .collect::<Option<_>>()?;
 
     Some(BusSchedule {
         earliest_depart,
@@ -35,12 +52,12 @@ pub fn generate(inp: &str) -> Option<BusSchedule> {
     })
 }
 
-#[allow(clippy::cast_precision_loss)]
-fn find_lowest_gt(target: i64, multiple: i64) -> i64 {
-    multiple * (target as f64 / multiple as f64).ceil() as i64
+// First departure of bus `id` at or after `time`
+fn next_departure(time: u128, id: u64) -> Option<u128> {
+    time.div_ceil(u128::from(id)).checked_mul(u128::from(id))
 }
 
-fn egcd(lhs: i64, rhs: i64) -> (i64, i64, i64) {
+fn egcd(lhs: i128, rhs: i128) -> (i128, i128, i128) {
     if lhs == 0 {
         (rhs, 0, 1)
     } else {
@@ -49,49 +66,182 @@ fn egcd(lhs: i64, rhs: i64) -> (i64, i64, i64) {
     }
 }
 
-fn mod_inv(lhs: i64, rhs: i64) -> Option<i64> {
+fn mod_inv(lhs: i128, rhs: i128) -> Option<i128> {
     let (g, x, _) = egcd(lhs, rhs);
     if g == 1 {
-        Some((x % rhs + rhs) % rhs)
+        Some(x.rem_euclid(rhs))
     } else {
         None
     }
 }
 
-fn chinese_remainder_theorem(residues: &[i64], modulii: &[i64]) -> Option<i64> {
-    let prod = modulii.iter().product::<i64>();
+// `lhs * rhs % modulus` without the intermediate product, which doesn't fit in
+// 128 bits once both sides are past 64 bits
+fn mul_mod(lhs: i128, rhs: i128, modulus: i128) -> i128 {
+    let add_mod = |a: i128, b: i128| {
+        if a >= modulus - b {
+            a - (modulus - b)
+        } else {
+            a + b
+        }
+    };
+
+    let mut base = lhs.rem_euclid(modulus);
+    let mut exp = rhs.rem_euclid(modulus);
+    let mut res = 0;
+
+    while exp > 0 {
+        if exp & 1 == 1 {
+            res = add_mod(res, base);
+        }
+        base = add_mod(base, base);
+        exp >>= 1;
+    }
+
+    res
+}
+
+// All timestamps `t` with `t % modulus == residue`. Only `new` builds one, so the
+// modulus is always positive and the residue below it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Congruence {
+    residue: i128,
+    modulus: i128,
+}
+
+impl Congruence {
+    pub fn new(residue: i128, modulus: i128) -> Option<Self> {
+        (modulus > 0).then(|| Self {
+            residue: residue.rem_euclid(modulus),
+            modulus,
+        })
+    }
+
+    pub const fn residue(self) -> i128 {
+        self.residue
+    }
+
+    pub const fn modulus(self) -> i128 {
+        self.modulus
+    }
+
+    // The moduli don't have to be coprime: the congruences agree iff their residues
+    // match modulo the gcd, and the combined solution repeats every lcm.
+    pub fn merge(self, other: Self) -> Result<Self, ScheduleError> {
+        let (g, _, _) = egcd(self.modulus, other.modulus);
+        let diff = other.residue - self.residue;
 
-    let mut sum = 0;
+        if diff % g != 0 {
+            return Err(ScheduleError::NoSolution);
+        }
 
-    for (&residue, &modulus) in residues.iter().zip(modulii) {
-        let p = prod / modulus;
-        sum += residue * mod_inv(p, modulus)? * p;
+        let step = other.modulus / g;
+        let inverse = mod_inv(self.modulus / g % step, step).ok_or(ScheduleError::NoSolution)?;
+        let factor = mul_mod(diff / g, inverse, step);
+
+        let modulus = self
+            .modulus
+            .checked_mul(step)
+            .ok_or(ScheduleError::Overflow)?;
+        let residue = self
+            .modulus
+            .checked_mul(factor)
+            .and_then(|it| it.checked_add(self.residue))
+            .ok_or(ScheduleError::Overflow)?;
+
+        Ok(Self { residue, modulus })
     }
+}
 
-    Some(sum % prod)
+pub fn chinese_remainder_theorem(congruences: &[Congruence]) -> Result<Congruence, ScheduleError> {
+    congruences.iter().try_fold(
+        Congruence {
+            residue: 0,
+            modulus: 1,
+        },
+        |acc, it| acc.merge(*it),
+    )
+}
+
+impl BusSchedule {
+    pub fn bus_ids(&self) -> Vec<u64> {
+        self.buses.iter().map(|it| it.id).collect()
+    }
+
+    // The first `count` departures of every bus at or after `time`
+    pub fn next_departures(&self, time: u128, count: usize) -> Vec<(u64, Vec<u128>)> {
+        self.buses
+            .iter()
+            .map(|bus| {
+                let departures = successors(next_departure(time, bus.id), |it| {
+                    it.checked_add(u128::from(bus.id))
+                })
+                .take(count)
+                .collect();
+                (bus.id, departures)
+            })
+            .collect()
+    }
+
+    // Timestamps at which every bus in `ids` departs `order` minutes after it
+    pub fn alignment(&self, ids: &[u64]) -> Result<Congruence, ScheduleError> {
+        if let Some(id) = ids
+            .iter()
+            .find(|id| self.buses.iter().all(|it| it.id != **id))
+        {
+            return Err(ScheduleError::UnknownBus(*id));
+        }
+
+        let congruences = self
+            .buses
+            .iter()
+            .filter(|it| ids.contains(&it.id))
+            .filter_map(|it| Congruence::new(-i128::from(it.order), i128::from(it.id)))
+            .collect::<Vec<_>>();
+
+        chinese_remainder_theorem(&congruences)
+    }
+
+    pub fn alignment_period(&self, ids: &[u64]) -> Result<u128, ScheduleError> {
+        self.alignment(ids).map(|it| it.modulus.unsigned_abs())
+    }
+
+    pub fn alignments_between(
+        &self,
+        ids: &[u64],
+        start: u128,
+        end: u128,
+    ) -> Result<Vec<u128>, ScheduleError> {
+        let alignment = self.alignment(ids)?;
+        let residue = alignment.residue.unsigned_abs();
+        let modulus = alignment.modulus.unsigned_abs();
+
+        // Both are below 2^127, so the sum can't overflow
+        let offset = (residue + modulus - start % modulus) % modulus;
+
+        Ok(
+            successors(start.checked_add(offset), |it| it.checked_add(modulus))
+                .take_while(|it| *it < end)
+                .collect(),
+        )
+    }
 }
 
 #[aoc(day13, part1)]
-pub fn part1(v: &BusSchedule) -> Option<i64> {
+pub fn part1(v: &BusSchedule) -> Option<u128> {
     let depart = v.earliest_depart;
 
     v.buses
         .iter()
-        .map(|it| {
-            let time = find_lowest_gt(depart, it.id);
-            (it.id, time)
-        })
-        .sorted_by_key(|(_, time)| *time)
-        .next()
-        .map(|(id, time)| id * (time - depart))
+        .filter_map(|it| Some((it.id, next_departure(depart, it.id)?)))
+        .min_by_key(|(_, time)| *time)
+        .map(|(id, time)| u128::from(id) * (time - depart))
 }
 
 #[aoc(day13, part2)]
-pub fn part2(v: &BusSchedule) -> Option<i64> {
-    let ids = v.buses.iter().map(|it| it.id).collect_vec();
-    let order = v.buses.iter().map(|it| it.id - it.order).collect_vec();
-
-    chinese_remainder_theorem(&order, &ids)
+pub fn part2(v: &BusSchedule) -> Result<u128, ScheduleError> {
+    v.alignment(&v.bus_ids())
+        .map(|it| it.residue.unsigned_abs())
 }
 
 #[cfg(test)]
@@ -116,7 +266,7 @@ mod tests {
         let Some(data) = generate(inp) else {
             panic!("Could not parse test input")
         };
-        assert_eq!(Some(3417), part2(&data));
+        assert_eq!(Ok(3417), part2(&data));
     }
 
     #[test]
@@ -127,7 +277,7 @@ mod tests {
         let Some(data) = generate(inp) else {
             panic!("Could not parse test input")
         };
-        assert_eq!(Some(754_018), part2(&data));
+        assert_eq!(Ok(754_018), part2(&data));
     }
 
     #[test]
@@ -138,7 +288,7 @@ mod tests {
         let Some(data) = generate(inp) else {
             panic!("Could not parse test input")
         };
-        assert_eq!(Some(779_210), part2(&data));
+        assert_eq!(Ok(779_210), part2(&data));
     }
 
     #[test]
@@ -149,7 +299,7 @@ mod tests {
         let Some(data) = generate(inp) else {
             panic!("Could not parse test input")
         };
-        assert_eq!(Some(1_261_476), part2(&data));
+        assert_eq!(Ok(1_261_476), part2(&data));
     }
 
     #[test]
@@ -160,6 +310,176 @@ mod tests {
         let Some(data) = generate(inp) else {
             panic!("Could not parse test input")
         };
-        assert_eq!(Some(1_202_161_486), part2(&data));
+        assert_eq!(Ok(1_202_161_486), part2(&data));
+    }
+
+    #[test]
+    fn test_non_coprime_ids() {
+        let inp = "0
+4,x,6";
+        let Some(data) = generate(inp) else {
+            panic!("Could not parse test input")
+        };
+        assert_eq!(Ok(4), part2(&data));
+
+        let Some(data) = generate("0\n4,6") else {
+            panic!("Could not parse test input")
+        };
+        assert_eq!(Err(ScheduleError::NoSolution), part2(&data));
+
+        let congruences = [
+            Congruence::new(2, 6),
+            Congruence::new(8, 10),
+            Congruence::new(-7, 15),
+        ];
+        let Some(mut congruences) = congruences.iter().copied().collect::<Option<Vec<_>>>() else {
+            panic!("Invalid congruence")
+        };
+        assert_eq!(
+            Congruence::new(8, 30).ok_or(ScheduleError::NoSolution),
+            chinese_remainder_theorem(&congruences)
+        );
+
+        congruences.extend(Congruence::new(1, 4));
+        assert_eq!(
+            Err(ScheduleError::NoSolution),
+            chinese_remainder_theorem(&congruences)
+        );
+        assert_eq!(None, Congruence::new(1, 0));
+        assert_eq!(None, Congruence::new(1, -3));
+        assert_eq!(
+            Some((4, 7)),
+            Congruence::new(-3, 7).map(|it| (it.residue(), it.modulus()))
+        );
+    }
+
+    #[test]
+    fn test_large_ids() {
+        let inp = "0
+999999999989,x,999999999959,999999999961";
+        let Some(data) = generate(inp) else {
+            panic!("Could not parse test input")
+        };
+        let Ok(time) = part2(&data) else {
+            panic!("No solution found")
+        };
+
+        assert!(time > u128::from(u64::MAX));
+        for bus in &data.buses {
+            assert_eq!(0, (time + u128::from(bus.order)) % u128::from(bus.id));
+        }
+
+        // The lcm of these is about 2^160
+        let inp = "0
+4000000007,x,4000000009,4000000037,4000000039,4000000043";
+        let Some(data) = generate(inp) else {
+            panic!("Could not parse test input")
+        };
+        assert_eq!(Err(ScheduleError::Overflow), part2(&data));
+    }
+
+    #[test]
+    fn test_ids_near_u64_max() {
+        // The two largest 64 bit primes, their product doesn't fit in an i128
+        let inp = "0
+18446744073709551557,18446744073709551533";
+        let Some(data) = generate(inp) else {
+            panic!("Could not parse test input")
+        };
+        assert_eq!(Err(ScheduleError::Overflow), part2(&data));
+
+        let inp = "0
+3,18446744073709551557";
+        let Some(data) = generate(inp) else {
+            panic!("Could not parse test input")
+        };
+        let Ok(time) = part2(&data) else {
+            panic!("No solution found")
+        };
+
+        assert!(time > u128::from(u64::MAX));
+        for bus in &data.buses {
+            assert_eq!(0, (time + u128::from(bus.order)) % u128::from(bus.id));
+        }
+
+        assert_eq!(2, mul_mod(i128::MAX - 1, i128::MAX - 2, i128::MAX));
+    }
+
+    #[test]
+    fn test_timetable() {
+        let inp = "939
+7,13,x,x,59,x,31,19";
+        let Some(data) = generate(inp) else {
+            panic!("Could not parse test input")
+        };
+
+        let departures = data.next_departures(939, 3);
+        assert_eq!((7, vec![945, 952, 959]), departures[0]);
+        assert_eq!((59, vec![944, 1003, 1062]), departures[2]);
+        assert_eq!(vec![(19, vec![950])], data.next_departures(950, 1)[4..]);
+
+        assert_eq!(Ok(7 * 13), data.alignment_period(&[7, 13]));
+        assert_eq!(
+            Ok(7 * 13 * 59 * 31 * 19),
+            data.alignment_period(&data.bus_ids())
+        );
+        assert_eq!(
+            Err(ScheduleError::UnknownBus(11)),
+            data.alignment_period(&[7, 11])
+        );
+
+        // 7 departs at t and 13 at t + 1
+        let times = data.alignments_between(&[7, 13], 0, 300);
+        assert_eq!(Ok(vec![77, 168, 259]), times);
+
+        let times = data.alignments_between(&data.bus_ids(), 1_000_000, 2_000_000);
+        assert_eq!(Ok(vec![1_068_781]), times);
+
+        // Stepping past the last alignment must stop instead of wrapping around
+        let Ok(times) = data.alignments_between(&[7, 13], u128::MAX - 200, u128::MAX) else {
+            panic!("No alignment found")
+        };
+        assert_eq!(2, times.len());
+        assert!(times
+            .iter()
+            .all(|it| *it >= u128::MAX - 200 && it % 91 == 77));
+        assert_eq!(
+            Ok(Vec::new()),
+            data.alignments_between(&[7, 13], u128::MAX - 1, u128::MAX)
+        );
+    }
+
+    #[test]
+    fn test_invalid_ids() {
+        assert!(generate("939\n7,0,x").is_none());
+        assert!(generate("939\n7,-13,x").is_none());
+        assert!(generate("939\n7,y,x").is_none());
+        assert!(generate("-939\n7,13").is_none());
+    }
+
+    #[test]
+    fn test_extreme_timestamps() {
+        // 2^53 + 1 is the first integer an f64 can't represent
+        let inp = "9007199254740993
+7,x,13";
+        let Some(data) = generate(inp) else {
+            panic!("Could not parse test input")
+        };
+        assert_eq!(Some(7 * 2), part1(&data));
+        assert_eq!(
+            vec![9_007_199_254_740_995, 9_007_199_254_741_002],
+            data.next_departures(9_007_199_254_740_993, 2)[0].1
+        );
+
+        let inp = format!("{}\n{},x,2", u128::MAX - 2, u64::MAX);
+        let Some(data) = generate(&inp) else {
+            panic!("Could not parse test input")
+        };
+        assert_eq!(Some(2), part1(&data));
+
+        // Both buses run out of timestamps right after their first departure
+        let departures = data.next_departures(u128::MAX - 2, 3);
+        assert_eq!(vec![u128::MAX], departures[0].1);
+        assert_eq!(vec![u128::MAX - 1], departures[1].1);
     }
 }
@@ -1,18 +1,107 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 
 use aoc_runner_derive::{aoc, aoc_generator};
 use parse_display::{Display as PDisplay, FromStr as PFromStr};
 
-#[derive(PDisplay, PFromStr)]
-#[display("mask = {mask}")]
+const MASK_LEN: usize = 36;
+
+#[derive(PDisplay, Clone, Debug, PartialEq, Eq)]
+pub enum ProgramError {
+    #[display("Mask has {0} bits instead of 36")]
+    InvalidMaskLength(usize),
+
+    #[display("Invalid mask character '{0}'")]
+    InvalidMaskCharacter(char),
+
+    #[display("Invalid instruction '{0}'")]
+    InvalidInstruction(String),
+
+    #[display("Memory access before the first mask")]
+    MissingMask,
+}
+
+impl std::error::Error for ProgramError {}
+
+// `and` keeps every bit that isn't forced to 0, `or` sets the forced 1s and
+// `floating` marks the X bits
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 pub struct Mask {
-    mask: String,
+    and: u64,
+    or: u64,
+    floating: u64,
+}
+
+impl FromStr for Mask {
+    type Err = ProgramError;
+
+    fn from_str(inp: &str) -> Result<Self, Self::Err> {
+        let len = inp.chars().count();
+        if len != MASK_LEN {
+            return Err(ProgramError::InvalidMaskLength(len));
+        }
+
+        inp.chars().try_fold(Self::default(), |acc, c| {
+            let (and, or, floating) = (acc.and << 1, acc.or << 1, acc.floating << 1);
+
+            match c {
+                '0' => Ok(Self { and, or, floating }),
+                '1' => Ok(Self {
+                    and: and | 1,
+                    or: or | 1,
+                    floating,
+                }),
+                'X' => Ok(Self {
+                    and: and | 1,
+                    or,
+                    floating: floating | 1,
+                }),
+                _ => Err(ProgramError::InvalidMaskCharacter(c)),
+            }
+        })
+    }
+}
+
+impl fmt::Display for Mask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bits = (0..MASK_LEN).rev().map(|bit| {
+            if self.floating >> bit & 1 == 1 {
+                'X'
+            } else if self.or >> bit & 1 == 1 {
+                '1'
+            } else {
+                '0'
+            }
+        });
+
+        write!(f, "{}", bits.collect::<String>())
+    }
+}
+
+impl Mask {
+    fn apply_to_value(self, value: u64) -> u64 {
+        value & self.and | self.or
+    }
+
+    // Every address the masked index decodes to, one per subset of the floating bits
+    fn apply_to_index(self, index: u64) -> impl Iterator<Item = u64> {
+        let base = (index | self.or) & !self.floating;
+        let floating = self.floating;
+
+        let mut subset = Some(floating);
+        std::iter::from_fn(move || {
+            let cur = subset?;
+            subset = (cur != 0).then(|| (cur - 1) & floating);
+            Some(base | cur)
+        })
+    }
 }
 
 #[derive(PDisplay, PFromStr)]
 #[display("mem[{index}] = {value}")]
 pub struct MemAccess {
-    index: i64,
+    index: u64,
     value: u64,
 }
 
@@ -22,91 +111,40 @@ pub struct Initialization {
 }
 
 #[aoc_generator(day14)]
-pub fn generate(inp: &str) -> Vec<Initialization> {
+pub fn generate(inp: &str) -> Result<Vec<Initialization>, ProgramError> {
     let mut res = Vec::new();
 
     for l in inp.lines() {
-        if let Ok(mask) = l.parse::<Mask>() {
+        if let Some(mask) = l.strip_prefix("mask = ") {
             res.push(Initialization {
-                mask,
+                mask: mask.parse()?,
                 accesses: Vec::new(),
             });
         } else if let Ok(access) = l.parse::<MemAccess>() {
-            if let Some(init) = res.last_mut() {
-                init.accesses.push(access);
-            }
+            res.last_mut()
+                .ok_or(ProgramError::MissingMask)?
+                .accesses
+                .push(access);
+        } else {
+            return Err(ProgramError::InvalidInstruction(l.to_string()));
         }
     }
 
-    res
+    Ok(res)
 }
 
-type Ram = HashMap<i64, u64>;
-
-fn apply_value_bitmask(inp: &str, mask: &str) -> String {
-    inp.chars()
-        .zip(mask.chars())
-        .map(|(l, r)| if r == 'X' { l } else { r })
-        .collect()
-}
-
-fn apply_index_bitmask(inp: &str, mask: &str) -> String {
-    inp.chars()
-        .zip(mask.chars())
-        .map(|(l, r)| match r {
-            '0' => l,
-            _ => r,
-        })
-        .collect()
-}
+type Ram = HashMap<u64, u64>;
 
 fn init_memory(init: &Initialization, memory: &mut Ram) {
-    let mask = &init.mask.mask;
-
-    init.accesses.iter().fold(memory, |acc, it| {
-        let new_val = apply_value_bitmask(&format!("{:036b}", it.value), mask);
-        if let Ok(new_val) = u64::from_str_radix(&new_val, 2) {
-            acc.insert(it.index, new_val);
-        }
-
-        acc
-    });
-}
-
-fn to_padded_binary_string(inp: usize, padding: usize) -> String {
-    format!("{inp:0>padding$b}")
-}
-
-fn generate_floating_combinations(num_floating: usize) -> Vec<String> {
-    (0..2_usize.pow(num_floating as u32))
-        .map(|it| to_padded_binary_string(it, num_floating))
-        .collect()
+    for acc in &init.accesses {
+        memory.insert(acc.index, init.mask.apply_to_value(acc.value));
+    }
 }
 
 fn init_memory_v2(init: &Initialization, memory: &mut Ram) {
-    let mask = &init.mask.mask;
-
     for acc in &init.accesses {
-        let new_index = apply_index_bitmask(&format!("{:036b}", acc.index), mask);
-
-        let num_floating = new_index.chars().filter(|it| *it == 'X').count();
-        let combinations = generate_floating_combinations(num_floating);
-
-        for comb in combinations {
-            let mut chrs = comb.chars();
-            let cur_val = new_index.to_string();
-
-            let new_str = cur_val
-                .chars()
-                .filter_map(|it| match it {
-                    'X' => chrs.next(),
-                    _ => Some(it),
-                })
-                .collect::<String>();
-
-            if let Ok(idx) = i64::from_str_radix(&new_str, 2) {
-                memory.insert(idx, acc.value);
-            }
+        for idx in init.mask.apply_to_index(acc.index) {
+            memory.insert(idx, acc.value);
         }
     }
 }
@@ -117,7 +155,7 @@ fn run_initialization(
     init_func: impl Fn(&Initialization, &mut Ram),
 ) -> u64 {
     init_sequence.iter().for_each(|it| init_func(it, memory));
-    memory.iter().map(|(_, val)| *val).sum()
+    memory.values().sum()
 }
 
 #[aoc(day14, part1)]
@@ -141,7 +179,9 @@ mem[8] = 11
 mem[7] = 101
 mem[8] = 0";
 
-        let data = generate(inp);
+        let Ok(data) = generate(inp) else {
+            panic!("Could not parse test input")
+        };
         assert_eq!(165, part1(&data));
     }
 
@@ -152,7 +192,52 @@ mem[42] = 100
 mask = 00000000000000000000000000000000X0XX
 mem[26] = 1";
 
-        let data = generate(inp);
+        let Ok(data) = generate(inp) else {
+            panic!("Could not parse test input")
+        };
         assert_eq!(208, part2(&data));
     }
+
+    #[test]
+    fn test_mask() {
+        let inp = "000000000000000000000000000000X1001X";
+        let Ok(mask) = inp.parse::<Mask>() else {
+            panic!("Could not parse test input")
+        };
+        assert_eq!(inp, mask.to_string());
+        assert_eq!(0b1_0010, mask.or);
+        assert_eq!(0b10_0001, mask.floating);
+
+        let mut addresses = mask.apply_to_index(42).collect::<Vec<_>>();
+        addresses.sort_unstable();
+        assert_eq!(vec![26, 27, 58, 59], addresses);
+
+        let Ok(mask) = "XXXXXXXXXXXXXXXXXXXXXXXXXXXXX1XXXX0X".parse::<Mask>() else {
+            panic!("Could not parse test input")
+        };
+        assert_eq!(73, mask.apply_to_value(11));
+        assert_eq!(64, mask.apply_to_value(0));
+        // Bits above the mask are cleared
+        assert_eq!(0b100_0000, mask.apply_to_value(1 << 40));
+    }
+
+    #[test]
+    fn test_invalid_program() {
+        assert_eq!(
+            Some(ProgramError::InvalidMaskLength(3)),
+            generate("mask = X10").err()
+        );
+        assert_eq!(
+            Some(ProgramError::InvalidMaskCharacter('2')),
+            generate(&format!("mask = {}2", "X".repeat(35))).err()
+        );
+        assert_eq!(
+            Some(ProgramError::MissingMask),
+            generate("mem[8] = 11").err()
+        );
+        assert_eq!(
+            Some(ProgramError::InvalidInstruction("mem[8] = x".to_string())),
+            generate(&format!("mask = {}\nmem[8] = x", "X".repeat(36))).err()
+        );
+    }
 }
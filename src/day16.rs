@@ -107,7 +107,7 @@ fn try_remove_single_rule(rule: &FieldRules, remove_from: &mut [Vec<&FieldRules>
             all_rules
                 .iter()
                 .position(|it| *it == rule)
-                .map_or(false, |idx| {
+                .is_some_and(|idx| {
                     all_rules.remove(idx);
                     true
                 })
@@ -1,5 +1,8 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryInto;
+
 use aoc_runner_derive::{aoc, aoc_generator};
-use itertools::iproduct;
+use itertools::{iproduct, Itertools};
 
 pub struct SimulationContext {
     num_steps: usize,
@@ -11,221 +14,309 @@ impl SimulationContext {
     }
 }
 
-type MapElements3D = Vec<Vec<Vec<bool>>>;
-type MapElements4D = Vec<MapElements3D>;
-
-#[derive(Clone)]
-pub struct Data3d {
-    dimension: usize,
-    elements: MapElements3D,
+pub struct Seed {
+    active: Vec<(i64, i64)>,
 }
 
-impl Data3d {
-    pub const fn new(dimension: usize, elements: MapElements3D) -> Self {
-        Self {
-            dimension,
-            elements,
-        }
+// The seed is a 2D slice, so every dimension past the first two is symmetric under
+// negation and under permutation with the other hidden dimensions. Only cells whose
+// hidden coordinates are non-negative and sorted are stored; each of them stands for
+// all cells in its orbit.
+const SEED_DIMS: usize = 2;
+
+fn canonical<const N: usize>(mut cell: [i64; N]) -> [i64; N] {
+    let hidden = &mut cell[SEED_DIMS.min(N)..];
+    for coord in hidden.iter_mut() {
+        *coord = coord.abs();
     }
-}
+    hidden.sort_unstable();
 
-#[derive(Clone)]
-pub struct Data4d {
-    dimension: usize,
-    elements: MapElements4D,
+    cell
 }
 
-impl Data4d {
-    pub const fn new(dimension: usize, elements: MapElements4D) -> Self {
-        Self {
-            dimension,
-            elements,
-        }
-    }
+fn factorial(n: usize) -> u64 {
+    (1..=n as u64).product()
 }
 
-fn run_iteration_3d(
-    prev_map: &[Vec<Vec<bool>>],
-    start: usize,
-    end: usize,
-    delta: &[(i64, i64, i64)],
-) -> MapElements3D {
-    let mut next_map_elements = prev_map.to_owned();
-
-    for (x, y, z) in iproduct!(start..end, start..end, start..end) {
-        let cur_state = prev_map[x][y][z];
+fn multiplicity<const N: usize>(cell: &[i64; N]) -> u64 {
+    let hidden = &cell[SEED_DIMS.min(N)..];
 
-        let alive = delta
+    let signs = 1 << hidden.iter().filter(|it| **it != 0).count();
+    let permutations = factorial(hidden.len())
+        / hidden
             .iter()
-            .filter(|(dx, dy, dz)| {
-                #[allow(clippy::cast_possible_wrap)]
-                let x = ((x as i64) + dx) as usize;
+            .dedup_with_count()
+            .map(|(count, _)| factorial(count))
+            .product::<u64>();
 
-                #[allow(clippy::cast_possible_wrap)]
-                let y = ((y as i64) + dy) as usize;
+    signs * permutations
+}
 
-                #[allow(clippy::cast_possible_wrap)]
-                let z = ((z as i64) + dz) as usize;
+#[derive(Clone)]
+pub struct PocketDimension<const N: usize> {
+    active: HashSet<[i64; N]>,
+}
 
-                prev_map[x][y][z]
+impl<const N: usize> PocketDimension<N> {
+    pub fn from_seed(seed: &Seed) -> Self {
+        let active = seed
+            .active
+            .iter()
+            .map(|(row, col)| {
+                let mut cell = [0; N];
+                for (coord, value) in cell.iter_mut().zip([*row, *col].iter()) {
+                    *coord = *value;
+                }
+                canonical(cell)
             })
-            .count();
+            .collect();
 
-        if cur_state && alive != 2 && alive != 3 {
-            next_map_elements[x][y][z] = false;
-        } else if !cur_state && alive == 3 {
-            next_map_elements[x][y][z] = true;
-        }
+        Self { active }
     }
 
-    next_map_elements
-}
+    // Every active cell c adds its orbit size to the canonical form of each of its
+    // neighbours. Dividing by the orbit size of the target then gives the number of
+    // active neighbours of any single cell in that orbit.
+    fn step(&self, delta: &[[i64; N]]) -> Self {
+        let mut weighted_counts = HashMap::<[i64; N], u64>::new();
 
-fn run_iteration_4d(
-    prev_map: &[Vec<Vec<Vec<bool>>>],
-    start: usize,
-    end: usize,
-    delta: &[(i64, i64, i64, i64)],
-) -> MapElements4D {
-    let mut next_map_elements = prev_map.to_owned();
+        for cell in &self.active {
+            let weight = multiplicity(cell);
 
-    for (x, y, z, w) in iproduct!(start..end, start..end, start..end, start..end) {
-        let cur_state = prev_map[x][y][z][w];
+            for offset in delta {
+                let mut neighbor = *cell;
+                for (coord, d) in neighbor.iter_mut().zip(offset) {
+                    *coord += d;
+                }
 
-        let alive = delta
-            .iter()
-            .filter(|(dx, dy, dz, dw)| {
-                #[allow(clippy::cast_possible_wrap)]
-                let x = ((x as i64) + dx) as usize;
+                *weighted_counts.entry(canonical(neighbor)).or_default() += weight;
+            }
+        }
 
-                #[allow(clippy::cast_possible_wrap)]
-                let y = ((y as i64) + dy) as usize;
+        let active = weighted_counts
+            .into_iter()
+            .filter(|(cell, weighted)| {
+                let alive = weighted / multiplicity(cell);
+                alive == 3 || (alive == 2 && self.active.contains(cell))
+            })
+            .map(|(cell, _)| cell)
+            .collect();
 
-                #[allow(clippy::cast_possible_wrap)]
-                let z = ((z as i64) + dz) as usize;
+        Self { active }
+    }
 
-                #[allow(clippy::cast_possible_wrap)]
-                let w = ((w as i64) + dw) as usize;
+    pub fn count_active(&self) -> usize {
+        self.active.iter().map(multiplicity).sum::<u64>() as usize
+    }
 
-                prev_map[x][y][z][w]
-            })
-            .count();
+    pub fn is_active(&self, cell: [i64; N]) -> bool {
+        self.active.contains(&canonical(cell))
+    }
 
-        if cur_state && alive != 2 && alive != 3 {
-            next_map_elements[x][y][z][w] = false;
-        } else if !cur_state && alive == 3 {
-            next_map_elements[x][y][z][w] = true;
+    fn slice_cell(row: i64, col: i64, hidden: &[i64]) -> [i64; N] {
+        let mut cell = [0; N];
+        for (coord, value) in cell.iter_mut().zip([row, col].iter().chain(hidden)) {
+            *coord = *value;
         }
+        cell
     }
 
-    next_map_elements
-}
+    fn non_empty_slices(&self) -> Vec<Vec<i64>> {
+        let Some((low, high)) = self.bounds() else {
+            return Vec::new();
+        };
+
+        let slice_has_active = |hidden: &Vec<i64>| {
+            iproduct!(low[0]..=high[0], low[1]..=high[1])
+                .any(|(row, col)| self.is_active(Self::slice_cell(row, col, hidden)))
+        };
+
+        (SEED_DIMS..N)
+            .map(|dim| low[dim]..=high[dim])
+            .multi_cartesian_product()
+            .filter(slice_has_active)
+            .collect()
+    }
 
-fn count_alive_cells(elem: &[Vec<Vec<bool>>]) -> usize {
-    elem.iter().flatten().flatten().filter(|it| **it).count()
-}
+    pub fn render_slice(&self, hidden: &[i64]) -> String {
+        let header = hidden_label(hidden);
+        let Some((low, high)) = self.bounds() else {
+            return header;
+        };
+
+        let rows = (low[0]..=high[0]).map(|row| {
+            (low[1]..=high[1])
+                .map(|col| {
+                    if self.is_active(Self::slice_cell(row, col, hidden)) {
+                        '#'
+                    } else {
+                        '.'
+                    }
+                })
+                .collect::<String>()
+        });
+
+        // A plain 2D grid has no hidden coordinates to label
+        let header = (!hidden.is_empty()).then_some(header);
+        header.into_iter().chain(rows).join("\n")
+    }
 
-pub fn run_simulation_steps_3d(map_data: &Data3d, context: &SimulationContext) -> usize {
-    let start = 1;
-    let end = map_data.dimension - 2;
+    pub fn summary(&self) -> String {
+        let Some((low, high)) = self.bounds() else {
+            return "bounds: none\nactive: 0".to_string();
+        };
 
-    let delta = build_delta_3d();
+        let bounds = [("y", 0), ("x", 1)]
+            .iter()
+            .map(|(name, dim)| format!("{name}={}..{}", low[*dim], high[*dim]))
+            .chain(
+                (SEED_DIMS..N)
+                    .map(|dim| format!("{}={}..{}", dimension_name(dim), low[dim], high[dim])),
+            )
+            .join(", ");
+
+        let per_slice = self
+            .non_empty_slices()
+            .iter()
+            .map(|hidden| {
+                let count = iproduct!(low[0]..=high[0], low[1]..=high[1])
+                    .filter(|(row, col)| self.is_active(Self::slice_cell(*row, *col, hidden)))
+                    .count();
+                format!("{}: {count}", hidden_label(hidden))
+            })
+            .join(", ");
 
-    let mut state = map_data.elements.clone();
-    for _ in 0..context.num_steps {
-        state = run_iteration_3d(&state, start, end, &delta);
+        format!(
+            "bounds: {bounds}\nactive: {} ({per_slice})",
+            self.count_active()
+        )
     }
 
-    count_alive_cells(&state)
-}
-
-pub fn run_simulation_steps_4d(map_data: &Data4d, context: &SimulationContext) -> usize {
-    let start = 1;
-    let end = map_data.dimension - 2;
+    pub fn render(&self) -> String {
+        self.non_empty_slices()
+            .iter()
+            .map(|hidden| self.render_slice(hidden))
+            .join("\n\n")
+    }
 
-    let delta = build_delta_4d();
+    pub fn bounds(&self) -> Option<([i64; N], [i64; N])> {
+        let mut cells = self.active.iter();
+        let first = *cells.next()?;
+
+        let (mut low, mut high) = cells.fold((first, first), |(mut low, mut high), cell| {
+            for ((lo, hi), coord) in low.iter_mut().zip(high.iter_mut()).zip(cell) {
+                *lo = (*lo).min(*coord);
+                *hi = (*hi).max(*coord);
+            }
+
+            (low, high)
+        });
+
+        let hidden_max = high[SEED_DIMS.min(N)..].iter().max().copied();
+        if let Some(hidden_max) = hidden_max {
+            for (lo, hi) in low.iter_mut().zip(high.iter_mut()).skip(SEED_DIMS) {
+                *lo = -hidden_max;
+                *hi = hidden_max;
+            }
+        }
 
-    let mut state = map_data.elements.clone();
-    for _ in 0..context.num_steps {
-        state = run_iteration_4d(&state, start, end, &delta);
+        Some((low, high))
     }
-
-    state.iter().map(|it| count_alive_cells(it)).sum()
 }
 
-fn build_delta_3d() -> Vec<(i64, i64, i64)> {
-    let mut res = Vec::new();
+fn build_delta<const N: usize>() -> Vec<[i64; N]> {
+    (0..N)
+        .map(|_| -1..=1)
+        .multi_cartesian_product()
+        .filter(|it| it.iter().any(|d| *d != 0))
+        .filter_map(|it| it.try_into().ok())
+        .collect()
+}
 
-    for (x, y, z) in iproduct!(-1..=1, -1..=1, -1..=1) {
-        if x == 0 && y == 0 && z == 0 {
-            continue;
-        }
+pub fn simulate<const N: usize>(seed: &Seed, context: &SimulationContext) -> PocketDimension<N> {
+    let delta = build_delta::<N>();
 
-        res.push((x, y, z));
+    let mut state = PocketDimension::<N>::from_seed(seed);
+    for _ in 0..context.num_steps {
+        state = state.step(&delta);
     }
 
-    res
+    state
 }
 
-fn build_delta_4d() -> Vec<(i64, i64, i64, i64)> {
-    let mut res = Vec::new();
-
-    for (x, y, z, w) in iproduct!(-1..=1, -1..=1, -1..=1, -1..=1) {
-        if x == 0 && y == 0 && z == 0 && w == 0 {
-            continue;
-        }
-
-        res.push((x, y, z, w));
+fn dimension_name(dim: usize) -> String {
+    match dim {
+        2 => "z".to_string(),
+        3 => "w".to_string(),
+        _ => format!("d{}", dim + 1),
     }
+}
 
-    res
+fn hidden_label(hidden: &[i64]) -> String {
+    hidden
+        .iter()
+        .enumerate()
+        .map(|(idx, value)| format!("{}={value}", dimension_name(idx + SEED_DIMS)))
+        .join(", ")
 }
 
-const NUM_ITER: usize = 6;
+pub fn render_cycles<const N: usize>(seed: &Seed, context: &SimulationContext) -> String {
+    let delta = build_delta::<N>();
 
-// 8 lines, growing by at most 1 per iteration, for 6 iterations, shifted into the middle
-const DIMENSION: usize = (8 + NUM_ITER) * 2;
+    let mut state = PocketDimension::<N>::from_seed(seed);
+    let mut report = Vec::new();
 
-#[aoc_generator(day17, part1)]
-pub fn generate_p1(inp: &str) -> Data3d {
-    let midway_pt = DIMENSION / 2;
+    for cycle in 0..=context.num_steps {
+        let title = match cycle {
+            0 => "Before any cycles:".to_string(),
+            1 => "After 1 cycle:".to_string(),
+            _ => format!("After {cycle} cycles:"),
+        };
 
-    let mut elements = vec![vec![vec![false; DIMENSION]; DIMENSION]; DIMENSION];
+        report.push(format!(
+            "{title}\n{}\n\n{}",
+            state.summary(),
+            state.render()
+        ));
 
-    for (row, l) in inp.lines().enumerate() {
-        for (col, c) in l.chars().enumerate() {
-            elements[midway_pt + row][midway_pt + col][midway_pt] = c == '#';
-        }
+        state = state.step(&delta);
     }
 
-    Data3d::new(DIMENSION, elements)
+    report.join("\n\n\n")
 }
 
-#[aoc_generator(day17, part2)]
-pub fn generate_p2(inp: &str) -> Data4d {
-    let midway_pt = DIMENSION / 2;
-
-    let mut elements = vec![vec![vec![vec![false; DIMENSION]; DIMENSION]; DIMENSION]; DIMENSION];
+pub fn run_simulation_steps<const N: usize>(seed: &Seed, context: &SimulationContext) -> usize {
+    simulate::<N>(seed, context).count_active()
+}
 
-    for (row, l) in inp.lines().enumerate() {
-        for (col, c) in l.chars().enumerate() {
-            elements[midway_pt + row][midway_pt + col][midway_pt][midway_pt] = c == '#';
-        }
-    }
+const NUM_ITER: usize = 6;
 
-    Data4d::new(DIMENSION, elements)
+#[aoc_generator(day17)]
+pub fn generate(inp: &str) -> Seed {
+    let active = inp
+        .lines()
+        .enumerate()
+        .flat_map(|(row, l)| {
+            l.chars()
+                .enumerate()
+                .filter(|(_, c)| *c == '#')
+                .filter_map(move |(col, _)| Some((row.try_into().ok()?, col.try_into().ok()?)))
+        })
+        .collect();
+
+    Seed { active }
 }
 
 #[aoc(day17, part1)]
-pub fn part1(md: &Data3d) -> usize {
+pub fn part1(seed: &Seed) -> usize {
     let context = SimulationContext::new(NUM_ITER);
-    run_simulation_steps_3d(md, &context)
+    run_simulation_steps::<3>(seed, &context)
 }
 
 #[aoc(day17, part2)]
-pub fn part2(md: &Data4d) -> usize {
+pub fn part2(seed: &Seed) -> usize {
     let context = SimulationContext::new(NUM_ITER);
-    run_simulation_steps_4d(md, &context)
+    run_simulation_steps::<4>(seed, &context)
 }
 
 #[cfg(test)]
@@ -238,7 +329,7 @@ mod tests {
 ..#
 ###";
 
-        let data = generate_p1(inp);
+        let data = generate(inp);
         assert_eq!(112, part1(&data));
     }
 
@@ -248,7 +339,100 @@ mod tests {
 ..#
 ###";
 
-        let data = generate_p2(inp);
+        let data = generate(inp);
         assert_eq!(848, part2(&data));
     }
+
+    #[test]
+    fn test_2d_is_game_of_life() {
+        let glider = generate(
+            ".#.
+..#
+###",
+        );
+
+        for num_steps in 0..8 {
+            let context = SimulationContext::new(num_steps);
+            assert_eq!(5, run_simulation_steps::<2>(&glider, &context));
+        }
+    }
+
+    #[test]
+    fn test_world_grows_past_the_seed() {
+        let glider = generate(
+            ".#.
+..#
+###",
+        );
+
+        let state = simulate::<2>(&glider, &SimulationContext::new(40));
+        assert_eq!(Some(([10, 10], [12, 12])), state.bounds());
+    }
+
+    #[test]
+    fn test_bigger_seed() {
+        let inp = ".....#.....#
+##..........
+#.####....#.
+.#..#..#....
+#....#...#..
+#...#.##.##.
+.#.#.#.....#
+.....#.#....
+##.#.....#.#
+..#...#....#
+.....##.#.##
+#....##.#...";
+
+        let data = generate(inp);
+        assert_eq!(
+            931,
+            run_simulation_steps::<3>(&data, &SimulationContext::new(10))
+        );
+        assert_eq!(
+            6444,
+            run_simulation_steps::<4>(&data, &SimulationContext::new(8))
+        );
+    }
+
+    #[test]
+    fn test_high_dimensions() {
+        let inp = ".#.
+..#
+###";
+
+        let data = generate(inp);
+        let context = SimulationContext::new(NUM_ITER);
+        assert_eq!(5760, run_simulation_steps::<5>(&data, &context));
+        assert_eq!(35936, run_simulation_steps::<6>(&data, &context));
+    }
+
+    #[test]
+    fn test_render_slices() {
+        let inp = ".#.
+..#
+###";
+
+        let data = generate(inp);
+        let state = simulate::<3>(&data, &SimulationContext::new(1));
+        assert_eq!("z=-1\n#..\n..#\n.#.", state.render_slice(&[-1]));
+        assert_eq!("z=0\n#.#\n.##\n.#.", state.render_slice(&[0]));
+        assert_eq!(
+            "bounds: y=1..3, x=0..2, z=-1..1\nactive: 11 (z=-1: 3, z=0: 5, z=1: 3)",
+            state.summary()
+        );
+
+        let state = simulate::<2>(&data, &SimulationContext::new(0));
+        assert_eq!(inp, state.render_slice(&[]));
+        assert_eq!(inp, state.render());
+
+        let state = simulate::<4>(&data, &SimulationContext::new(1));
+        assert_eq!("z=-1, w=-1\n#..\n..#\n.#.", state.render_slice(&[-1, -1]));
+        assert_eq!(9, state.render().matches("z=").count());
+
+        let report = render_cycles::<3>(&data, &SimulationContext::new(2));
+        assert!(report.starts_with("Before any cycles:\nbounds: y=0..2, x=0..2, z=0..0\n"));
+        assert!(report.contains("After 1 cycle:\n"));
+        assert!(report.contains("After 2 cycles:\n"));
+    }
 }
@@ -1,265 +1,491 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::{iproduct, Itertools};
+use parse_display::Display as PDisplay;
 
-const GRID_SIZE: usize = 10;
+use super::grid::{Grid, Orientation, Pos};
 
-#[derive(Default, Clone, Debug)]
+#[derive(PDisplay, Debug, PartialEq, Eq)]
+pub enum PuzzleError {
+    #[display("Invalid tile title: '{0}'")]
+    InvalidTitle(String),
+
+    #[display("Invalid character '{chr}' in tile {id}")]
+    InvalidCharacter { id: usize, chr: char },
+
+    #[display("Tile {id} is not square: {rows} rows, but a row with {cols} columns")]
+    RaggedTile { id: usize, rows: usize, cols: usize },
+
+    #[display("Tile {id} has size {size}, expected {expected}")]
+    MismatchedTileSize {
+        id: usize,
+        size: usize,
+        expected: usize,
+    },
+
+    #[display("Tile {id} has size {size}, at most 64 is supported")]
+    TileTooLarge { id: usize, size: usize },
+
+    #[display("{tiles} tiles with {border_edges} unmatched edges do not form a rectangle")]
+    NotRectangular { tiles: usize, border_edges: usize },
+
+    #[display("No arrangement of the tiles matches all edges")]
+    NoArrangement,
+
+    #[display("Invalid character '{0}' in pattern, expected '#' or ' '")]
+    InvalidPattern(char),
+
+    #[display("Pattern does not contain any '#'")]
+    EmptyPattern,
+
+    #[display("No sea monsters found in the image")]
+    NoSeaMonsters,
+}
+
+impl std::error::Error for PuzzleError {}
+
+#[derive(Clone, Debug)]
 pub struct MapTile {
     id: usize,
-    map: Vec<Vec<char>>,
+    map: Grid<char>,
 }
 
-const MONSTER_OFFSETS: &[(i64, i64); 15] = &[
-    (0, 0),
-    (1, 1),
-    (1, 4),
-    (0, 5),
-    (0, 6),
-    (1, 7),
-    (1, 10),
-    (0, 11),
-    (0, 12),
-    (1, 13),
-    (1, 16),
-    (0, 17),
-    (0, 18),
-    (-1, 18),
-    (0, 19),
-];
+const SEA_MONSTER: &str = "                  #
+#    ##    ##    ###
+ #  #  #  #  #  #";
 
-impl MapTile {
-    fn is_sea_monster_at(&self, x: usize, y: usize) -> bool {
-        MONSTER_OFFSETS.iter().all(|(dy, dx)| {
-            #[allow(clippy::cast_possible_wrap)]
-            let value = self.map[(y as i64 + dy) as usize][(x as i64 + dx) as usize];
-            value == '#'
-        })
-    }
+const CALM_WATER_COLOR: (u8, u8, u8) = (0, 48, 96);
+const ROUGH_WATER_COLOR: (u8, u8, u8) = (135, 206, 235);
+const HIGHLIGHT_COLOR: (u8, u8, u8) = (220, 40, 40);
+
+#[derive(Clone, Debug)]
+pub struct Pattern {
+    offsets: Vec<(usize, usize)>,
+    rows: usize,
+    cols: usize,
+}
+
+impl FromStr for Pattern {
+    type Err = PuzzleError;
 
-    fn replace_sea_monsters(&mut self) {
-        for (y, x) in iproduct!(1..self.map.len() - 1, 0..self.map.len() - 19) {
-            if self.is_sea_monster_at(x, y) {
-                for (dy, dx) in MONSTER_OFFSETS {
-                    #[allow(clippy::cast_possible_wrap)]
-                    let entry = &mut self.map[(y as i64 + dy) as usize][(x as i64 + dx) as usize];
-                    *entry = 'O';
+    fn from_str(inp: &str) -> Result<Self, Self::Err> {
+        let mut offsets = Vec::new();
+
+        for (row, line) in inp.lines().enumerate() {
+            for (col, chr) in line.chars().enumerate() {
+                match chr {
+                    '#' => offsets.push((row, col)),
+                    ' ' => {}
+                    _ => return Err(PuzzleError::InvalidPattern(chr)),
                 }
             }
         }
-    }
 
-    fn contains_sea_monster(&self) -> bool {
-        for (y, row) in self.map.iter().enumerate().skip(1).take(self.map.len() - 2) {
-            for x in 0..(row.len() - 19) {
-                if self.is_sea_monster_at(x, y) {
-                    return true;
-                }
-            }
+        let rows = offsets.iter().map(|(row, _)| row + 1).max();
+        let cols = offsets.iter().map(|(_, col)| col + 1).max();
+
+        match (rows, cols) {
+            (Some(rows), Some(cols)) => Ok(Self {
+                offsets,
+                rows,
+                cols,
+            }),
+            _ => Err(PuzzleError::EmptyPattern),
         }
+    }
+}
 
-        false
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PatternMatch {
+    pub orientation: Orientation,
+    pub row: usize,
+    pub col: usize,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct EdgeSignatures {
+    top: u64,
+    right: u64,
+    bottom: u64,
+    left: u64,
+}
+
+impl EdgeSignatures {
+    const fn all(self) -> [u64; 4] {
+        [self.top, self.right, self.bottom, self.left]
     }
+}
+
+fn signature(cells: impl Iterator<Item = char>) -> u64 {
+    cells.fold(0, |acc, it| (acc << 1) | u64::from(it == '#'))
+}
 
-    fn is_match(lhs: &[char], rhs: &[char]) -> bool {
-        lhs.len() == rhs.len() && lhs.iter().zip(rhs).all(|(l, r)| *l == *r)
+fn canonical_signature(signature: u64, len: usize) -> u64 {
+    let reversed = signature.reverse_bits() >> (64 - len);
+    signature.min(reversed)
+}
+
+impl fmt::Display for MapTile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.map)
     }
+}
 
-    fn matches_bottom(&self, other: &Self) -> bool {
-        self.map.last().map_or(false, |self_last| {
-            other
-                .map
-                .first()
-                .map_or(false, |other_first| Self::is_match(self_last, other_first))
-        })
+impl MapTile {
+    // PBM is black and white only, so sea monsters come out as plain rough water.
+    // `to_ppm` is the export that highlights them.
+    pub fn to_pbm(&self) -> String {
+        let pixels = self.map.rows().map(|row| {
+            row.iter()
+                .map(|it| if *it == '.' { '0' } else { '1' })
+                .join(" ")
+        });
+
+        format!(
+            "P1\n{} {}\n{}\n",
+            self.map.width(),
+            self.map.height(),
+            pixels.format("\n")
+        )
     }
 
-    fn matches_right(&self, other: &Self) -> bool {
-        let self_right = self
-            .map
-            .iter()
-            .filter_map(|it| it.last().copied())
-            .collect_vec();
+    pub fn to_ppm(&self) -> String {
+        let pixels = self.map.rows().map(|row| {
+            row.iter()
+                .map(|it| {
+                    let (r, g, b) = match it {
+                        '#' => ROUGH_WATER_COLOR,
+                        'O' => HIGHLIGHT_COLOR,
+                        _ => CALM_WATER_COLOR,
+                    };
+                    format!("{r} {g} {b}")
+                })
+                .join(" ")
+        });
+
+        format!(
+            "P3\n{} {}\n255\n{}\n",
+            self.map.width(),
+            self.map.height(),
+            pixels.format("\n")
+        )
+    }
+
+    fn oriented(&self, orientation: Orientation) -> Self {
+        Self {
+            id: self.id,
+            map: self.map.oriented(orientation),
+        }
+    }
+
+    fn edge_signatures(&self, orientation: Orientation) -> EdgeSignatures {
+        let (rows, cols) = self.map.oriented_dims(orientation);
+        let at = |row, col| *self.map.get_oriented(orientation, (row, col));
+
+        EdgeSignatures {
+            top: signature((0..cols).map(|col| at(0, col))),
+            right: signature((0..rows).map(|row| at(row, cols - 1))),
+            bottom: signature((0..cols).map(|col| at(rows - 1, col))),
+            left: signature((0..rows).map(|row| at(row, 0))),
+        }
+    }
 
-        let other_left = other
-            .map
+    fn is_pattern_at(&self, pattern: &Pattern, row: usize, col: usize) -> bool {
+        pattern
+            .offsets
             .iter()
-            .filter_map(|it| it.first().copied())
-            .collect_vec();
+            .all(|(dr, dc)| self.map[(row + dr, col + dc)] == '#')
+    }
 
-        Self::is_match(&self_right, &other_left)
+    fn pattern_positions(&self, pattern: &Pattern) -> Vec<Pos> {
+        let (Some(max_row), Some(max_col)) = (
+            self.map.height().checked_sub(pattern.rows),
+            self.map.width().checked_sub(pattern.cols),
+        ) else {
+            return Vec::new();
+        };
+
+        iproduct!(0..=max_row, 0..=max_col)
+            .filter(|(row, col)| self.is_pattern_at(pattern, *row, *col))
+            .collect()
+    }
+
+    fn highlight_pattern(&mut self, pattern: &Pattern, row: usize, col: usize) {
+        for (dr, dc) in &pattern.offsets {
+            self.map[(row + dr, col + dc)] = 'O';
+        }
     }
 }
 
-#[aoc_generator(day20)]
-pub fn generate(inp: &str) -> Vec<MapTile> {
-    inp.split("\n\n").fold(Vec::new(), |mut acc, it| {
-        let lines = &mut it.lines();
-        if let Some(title_line) = lines.next() {
-            if let Some(id) = title_line
-                .strip_prefix("Tile ")
-                .and_then(|it| it.strip_suffix(':'))
-                .and_then(|it| it.parse::<usize>().ok())
-            {
-                let mut map = vec![vec!['?'; GRID_SIZE]; GRID_SIZE];
-                for (y, l) in lines.enumerate() {
-                    for (x, chr) in l.char_indices() {
-                        match chr {
-                            '.' => map[y][x] = '.',
-                            '#' => map[y][x] = '#',
-                            _ => panic!("Invalid character for map"),
-                        }
+struct EdgeIndex {
+    signatures: Vec<Vec<(Orientation, EdgeSignatures)>>,
+    candidates: HashMap<u64, Vec<usize>>,
+    edge_len: usize,
+}
+
+impl EdgeIndex {
+    fn new(data: &[MapTile]) -> Self {
+        let signatures = data
+            .iter()
+            .map(|tile| {
+                Orientation::all()
+                    .map(|o| (o, tile.edge_signatures(o)))
+                    .collect_vec()
+            })
+            .collect_vec();
+
+        let edge_len = data.first().map_or(0, |it| it.map.width());
+
+        let mut candidates = HashMap::<u64, Vec<usize>>::new();
+        for (idx, tile_signatures) in signatures.iter().enumerate() {
+            if let Some((_, edges)) = tile_signatures.first() {
+                for edge in edges.all() {
+                    let entry = candidates
+                        .entry(canonical_signature(edge, edge_len))
+                        .or_default();
+                    if !entry.contains(&idx) {
+                        entry.push(idx);
                     }
                 }
-
-                let tile = MapTile { id, map };
-                acc.push(tile);
             }
         }
 
-        acc
-    })
-}
+        Self {
+            signatures,
+            candidates,
+            edge_len,
+        }
+    }
+
+    fn candidates_for(&self, signature: u64) -> &[usize] {
+        self.candidates
+            .get(&canonical_signature(signature, self.edge_len))
+            .map_or(&[], Vec::as_slice)
+    }
 
-fn flip_x(tile: &MapTile) -> MapTile {
-    let mut flipped = Vec::new();
+    fn count_unmatched_edges(&self, idx: usize) -> usize {
+        self.signatures[idx].first().map_or(0, |(_, edges)| {
+            edges
+                .all()
+                .iter()
+                .filter(|edge| self.candidates_for(**edge).len() == 1)
+                .count()
+        })
+    }
 
-    for row in &tile.map {
-        let flipped_row = row.iter().rev().copied().collect_vec();
-        flipped.push(flipped_row);
+    fn image_dims(&self) -> Result<(usize, usize), PuzzleError> {
+        let tiles = self.signatures.len();
+        let border_edges = (0..tiles)
+            .map(|idx| self.count_unmatched_edges(idx))
+            .sum::<usize>();
+
+        (1..=tiles)
+            .filter(|height| tiles.is_multiple_of(*height))
+            .map(|height| (tiles / height, height))
+            .find(|(width, height)| width >= height && 2 * (width + height) == border_edges)
+            .ok_or(PuzzleError::NotRectangular {
+                tiles,
+                border_edges,
+            })
     }
 
-    MapTile {
-        id: tile.id,
-        map: flipped,
+    fn corner_tiles(&self) -> Vec<usize> {
+        (0..self.signatures.len())
+            .filter(|idx| self.count_unmatched_edges(*idx) == 2)
+            .collect()
     }
 }
 
-fn flips_and_rotations(tile: &MapTile) -> Vec<MapTile> {
-    let dim = tile.map.len();
-
-    let mut res = Vec::new();
+#[derive(Copy, Clone, Debug)]
+struct Placement {
+    tile: usize,
+    orientation: Orientation,
+    edges: EdgeSignatures,
+}
 
-    let mut add_rotations = |tile: &MapTile| {
-        res.push(tile.clone());
-        {
-            let mut acc = tile.map.clone();
-            for (x, y) in iproduct!(0..dim, 1..=dim) {
-                acc[y - 1][x] = tile.map[dim - y][x];
-            }
+struct Assembly<'a> {
+    index: &'a EdgeIndex,
+    width: usize,
+    height: usize,
+    placed: Vec<Placement>,
+    used: Vec<bool>,
+}
 
-            let mt = MapTile {
-                id: tile.id,
-                map: acc,
-            };
-            res.push(mt);
+impl<'a> Assembly<'a> {
+    fn new(index: &'a EdgeIndex, width: usize, height: usize) -> Self {
+        Self {
+            index,
+            width,
+            height,
+            placed: Vec::new(),
+            used: vec![false; index.signatures.len()],
         }
-        {
-            let mut acc = tile.map.clone();
-            for (x, y) in iproduct!(1..=dim, 1..=dim) {
-                acc[y - 1][x - 1] = tile.map[dim - x][dim - y];
-            }
+    }
 
-            let mt = MapTile {
-                id: tile.id,
-                map: acc,
-            };
-            res.push(mt);
+    fn start_candidates(&self) -> Vec<usize> {
+        (0..self.index.signatures.len())
+            .sorted_by_key(|idx| std::cmp::Reverse(self.index.count_unmatched_edges(*idx)))
+            .collect()
+    }
+
+    // Every unused tile and orientation that fits the next free position
+    fn placements(&self) -> Vec<Placement> {
+        let pos = self.placed.len();
+        let left = (!pos.is_multiple_of(self.width)).then(|| self.placed[pos - 1].edges.right);
+        let top = (pos >= self.width).then(|| self.placed[pos - self.width].edges.bottom);
+
+        let candidates = left.or(top).map_or_else(
+            || self.start_candidates(),
+            |it| self.index.candidates_for(it).to_vec(),
+        );
+
+        candidates
+            .into_iter()
+            .filter(|tile| !self.used[*tile])
+            .flat_map(|tile| {
+                self.index.signatures[tile]
+                    .iter()
+                    .filter(move |(_, edges)| {
+                        left.is_none_or(|it| it == edges.left)
+                            && top.is_none_or(|it| it == edges.top)
+                    })
+                    .map(move |&(orientation, edges)| Placement {
+                        tile,
+                        orientation,
+                        edges,
+                    })
+            })
+            .collect()
+    }
+
+    // Backtracking with one frame per placed tile: the placements that fit its
+    // position and a cursor to the next one to try
+    fn fill(&mut self) -> bool {
+        let total = self.width * self.height;
+        if self.placed.len() == total {
+            return true;
         }
 
-        {
-            let mut acc = tile.map.clone();
-            for (x, y) in iproduct!(1..=dim, 0..dim) {
-                acc[x - 1][y] = tile.map[y][dim - x];
-            }
+        let mut stack = vec![(self.placements(), 0)];
 
-            let mt = MapTile {
-                id: tile.id,
-                map: acc,
+        while let Some((options, cursor)) = stack.last_mut() {
+            let Some(placement) = options.get(*cursor).copied() else {
+                stack.pop();
+                if let Some(prev) = self.placed.pop() {
+                    self.used[prev.tile] = false;
+                }
+                continue;
             };
-            res.push(mt);
-        }
-    };
+            *cursor += 1;
 
-    add_rotations(tile);
+            self.used[placement.tile] = true;
+            self.placed.push(placement);
+            if self.placed.len() == total {
+                return true;
+            }
 
-    let flipped = flip_x(tile);
-    add_rotations(&flipped);
+            stack.push((self.placements(), 0));
+        }
 
-    res
+        false
+    }
 }
 
-fn fill_grid(
-    x: usize,
-    y: usize,
-    data: &[MapTile],
-    visited: &mut HashSet<usize>,
-    grid: &mut Vec<Vec<MapTile>>,
-) -> bool {
-    if visited.len() == data.len() {
-        return true;
-    }
-
-    let unvisited = data
-        .iter()
-        .filter(|it| !visited.contains(&it.id))
-        .collect_vec();
-
-    for tile in unvisited {
-        let cur_id = tile.id;
-        visited.insert(cur_id);
-
-        let flips_rots = flips_and_rotations(tile);
-        for fr in &flips_rots {
-            let x_match = x == 0 || grid[y][x - 1].matches_right(fr);
-            let y_match = y == 0 || grid[y - 1][x].matches_bottom(fr);
-
-            if x_match && y_match {
-                grid[y][x] = fr.clone();
-
-                let next_x = (x + 1) % grid.len();
-                let next_y = if next_x == 0 { y + 1 } else { y };
-                if fill_grid(next_x, next_y, data, visited, grid) {
-                    return true;
-                }
-            }
+#[aoc_generator(day20)]
+pub fn generate(inp: &str) -> Result<Vec<MapTile>, PuzzleError> {
+    let tiles = inp
+        .split("\n\n")
+        .filter(|it| !it.trim().is_empty())
+        .map(parse_tile)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if let Some(first) = tiles.first() {
+        if let Some(tile) = tiles
+            .iter()
+            .find(|it| it.map.height() != first.map.height())
+        {
+            return Err(PuzzleError::MismatchedTileSize {
+                id: tile.id,
+                size: tile.map.height(),
+                expected: first.map.height(),
+            });
         }
-
-        visited.remove(&cur_id);
     }
 
-    false
+    Ok(tiles)
 }
 
-fn reconstruct_image(dim: usize, data: &[MapTile]) -> Vec<Vec<MapTile>> {
-    let mut grid = vec![vec![MapTile::default(); dim]; dim];
+fn parse_tile(inp: &str) -> Result<MapTile, PuzzleError> {
+    let lines = &mut inp.lines();
+
+    let title_line = lines.next().unwrap_or_default();
+    let id = title_line
+        .strip_prefix("Tile ")
+        .and_then(|it| it.strip_suffix(':'))
+        .and_then(|it| it.parse::<usize>().ok())
+        .ok_or_else(|| PuzzleError::InvalidTitle(title_line.to_string()))?;
+
+    let rows = lines
+        .map(|l| {
+            l.chars()
+                .map(|chr| match chr {
+                    '.' | '#' => Ok(chr),
+                    _ => Err(PuzzleError::InvalidCharacter { id, chr }),
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
 
-    let mut visited = HashSet::new();
+    let size = rows.len();
+    if size > 64 {
+        return Err(PuzzleError::TileTooLarge { id, size });
+    }
 
-    for tile in data {
-        let cur_id = tile.id;
-        visited.insert(cur_id);
+    Grid::from_rows(rows)
+        .filter(|it| size > 0 && it.width() == size)
+        .map(|map| MapTile { id, map })
+        .ok_or_else(|| PuzzleError::RaggedTile {
+            id,
+            rows: size,
+            cols: inp
+                .lines()
+                .skip(1)
+                .map(str::len)
+                .find(|it| *it != size)
+                .unwrap_or(0),
+        })
+}
 
-        let flips_rots = flips_and_rotations(tile);
-        for fr in &flips_rots {
-            grid[0][0] = fr.clone();
-            if fill_grid(1, 0, data, &mut visited, &mut grid) {
-                return grid;
-            }
-        }
+fn reconstruct_image(data: &[MapTile]) -> Result<Vec<Vec<MapTile>>, PuzzleError> {
+    let index = EdgeIndex::new(data);
+    let (width, height) = index.image_dims()?;
 
-        visited.clear();
+    let mut assembly = Assembly::new(&index, width, height);
+    if !assembly.fill() {
+        return Err(PuzzleError::NoArrangement);
     }
 
-    panic!("Wrong starting thingy")
+    Ok(assembly
+        .placed
+        .chunks(width)
+        .map(|row| {
+            row.iter()
+                .map(|it| data[it.tile].oriented(it.orientation))
+                .collect_vec()
+        })
+        .collect_vec())
 }
 
-fn merge_without_border(image: &[Vec<MapTile>]) -> Vec<Vec<char>> {
-    image.iter().fold(Vec::new(), |mut acc, cur_row| {
-        for idx in 1..GRID_SIZE - 1 {
+fn merge_without_border(image: &[Vec<MapTile>]) -> Grid<char> {
+    let rows = image.iter().fold(Vec::new(), |mut acc, cur_row| {
+        let tile_size = cur_row.first().map_or(0, |it| it.map.height());
+
+        for idx in 1..tile_size.saturating_sub(1) {
             let tmp = cur_row.iter().fold(Vec::new(), |inner_acc, cur_tile| {
-                let first_row = &cur_tile.map[idx];
+                let first_row = cur_tile.map.row(idx);
                 first_row.iter().dropping(1).dropping_back(1).fold(
                     inner_acc,
                     |mut inner_acc, it| {
@@ -273,41 +499,131 @@ fn merge_without_border(image: &[Vec<MapTile>]) -> Vec<Vec<char>> {
         }
 
         acc
-    })
+    });
+
+    Grid::from_rows(rows).unwrap_or_default()
 }
 
 #[aoc(day20, part1)]
-#[allow(clippy::cast_precision_loss)]
-pub fn part1(data: &[MapTile]) -> usize {
-    let grid_dim = (data.len() as f64).sqrt() as usize;
-    let image = reconstruct_image(grid_dim, data);
-
-    image[0][0].id
-        * image[0][grid_dim - 1].id
-        * image[grid_dim - 1][0].id
-        * image[grid_dim - 1][grid_dim - 1].id
+pub fn part1(data: &[MapTile]) -> Result<usize, PuzzleError> {
+    // In a single row it's the middle tiles that have two unmatched edges, so the
+    // corner count can only be trusted for images at least two tiles high
+    let index = EdgeIndex::new(data);
+    let corners = index.corner_tiles();
+    if corners.len() == 4 && index.image_dims().is_ok_and(|(_, height)| height >= 2) {
+        return Ok(corners.iter().map(|idx| data[*idx].id).product());
+    }
+
+    let image = reconstruct_image(data)?;
+
+    Ok(image
+        .first()
+        .into_iter()
+        .chain(image.last())
+        .flat_map(|row| row.first().into_iter().chain(row.last()))
+        .map(|it| it.id)
+        .unique()
+        .product())
 }
 
-#[aoc(day20, part2)]
-#[allow(clippy::cast_precision_loss)]
-pub fn part2(data: &[MapTile]) -> Option<usize> {
-    let grid_dim = (data.len() as f64).sqrt() as usize;
-    let image = reconstruct_image(grid_dim, data);
+fn assemble_image(data: &[MapTile]) -> Result<MapTile, PuzzleError> {
+    let image = reconstruct_image(data)?;
 
-    let merged = merge_without_border(&image);
+    Ok(MapTile {
+        id: 0,
+        map: merge_without_border(&image),
+    })
+}
+
+fn find_pattern(image: &MapTile, pattern: &Pattern) -> Vec<PatternMatch> {
+    Orientation::all()
+        .flat_map(|orientation| {
+            image
+                .oriented(orientation)
+                .pattern_positions(pattern)
+                .into_iter()
+                .map(move |(row, col)| PatternMatch {
+                    orientation,
+                    row,
+                    col,
+                })
+        })
+        .collect()
+}
+
+pub fn search_image(data: &[MapTile], pattern: &str) -> Result<Vec<PatternMatch>, PuzzleError> {
+    let pattern = pattern.parse::<Pattern>()?;
+    let image = assemble_image(data)?;
 
-    let combined_tile = MapTile { id: 0, map: merged };
+    Ok(find_pattern(&image, &pattern))
+}
+
+fn highlight_pattern_in_image(image: &MapTile, pattern: &Pattern) -> Option<MapTile> {
+    let matches = find_pattern(image, pattern);
+    let orientation = matches.first()?.orientation;
+
+    let mut highlighted = image.oriented(orientation);
+    for it in matches.iter().filter(|it| it.orientation == orientation) {
+        highlighted.highlight_pattern(pattern, it.row, it.col);
+    }
+
+    Some(highlighted)
+}
 
-    let mut frs = flips_and_rotations(&combined_tile);
-    let with_monsters = frs.iter_mut().find(|it| it.contains_sea_monster())?;
-    with_monsters.replace_sea_monsters();
+pub fn highlight_sea_monsters(data: &[MapTile]) -> Result<MapTile, PuzzleError> {
+    let pattern = SEA_MONSTER.parse::<Pattern>()?;
+    let image = assemble_image(data)?;
 
-    let water_roughness = with_monsters
-        .map
-        .iter()
-        .map(|it| it.iter().filter(|c| **c == '#').count())
-        .sum();
-    Some(water_roughness)
+    highlight_pattern_in_image(&image, &pattern).ok_or(PuzzleError::NoSeaMonsters)
+}
+
+fn render_tile_grid(image: &[Vec<MapTile>]) -> String {
+    let Some(first_row) = image.first() else {
+        return String::new();
+    };
+
+    let separator = format!(
+        "+{}+",
+        first_row
+            .iter()
+            .map(|it| "-".repeat(it.map.width()))
+            .join("+")
+    );
+
+    let mut lines = vec![separator.clone()];
+    for row in image {
+        let tile_size = row.first().map_or(0, |it| it.map.width());
+
+        let ids = row
+            .iter()
+            .map(|it| format!("{:<tile_size$.tile_size$}", it.id.to_string()))
+            .join("|");
+        lines.push(format!("|{ids}|"));
+
+        for idx in 0..tile_size {
+            let cells = row
+                .iter()
+                .map(|it| it.map.row(idx).iter().collect::<String>())
+                .join("|");
+            lines.push(format!("|{cells}|"));
+        }
+
+        lines.push(separator.clone());
+    }
+
+    lines.join("\n")
+}
+
+pub fn render_assembly(data: &[MapTile]) -> Result<String, PuzzleError> {
+    reconstruct_image(data).map(|image| render_tile_grid(&image))
+}
+
+#[aoc(day20, part2)]
+pub fn part2(data: &[MapTile]) -> Result<usize, PuzzleError> {
+    let with_monsters = highlight_sea_monsters(data)?;
+
+    let water_roughness = with_monsters.map.values().filter(|c| **c == '#').count();
+    Ok(water_roughness)
 }
 
 #[cfg(test)]
@@ -424,13 +740,232 @@ Tile 3079:
 
     #[test]
     fn test_part1() {
-        let data = generate(INP);
-        assert_eq!(20_899_048_083_289, part1(&data));
+        let Ok(data) = generate(INP) else {
+            panic!("Could not parse test input")
+        };
+        assert_eq!(Ok(20_899_048_083_289), part1(&data));
     }
 
     #[test]
     fn test_part2() {
-        let data = generate(INP);
-        assert_eq!(Some(273), part2(&data));
+        let Ok(data) = generate(INP) else {
+            panic!("Could not parse test input")
+        };
+        assert_eq!(Ok(273), part2(&data));
+    }
+
+    #[test]
+    fn test_corner_tiles() {
+        let Ok(data) = generate(INP) else {
+            panic!("Could not parse test input")
+        };
+        let corners = EdgeIndex::new(&data)
+            .corner_tiles()
+            .iter()
+            .map(|idx| data[*idx].id)
+            .sorted()
+            .collect_vec();
+        assert_eq!(vec![1171, 1951, 2971, 3079], corners);
+    }
+
+    #[test]
+    fn test_edge_signatures_match_oriented_tile() {
+        let Ok(data) = generate(INP) else {
+            panic!("Could not parse test input")
+        };
+        for orientation in Orientation::all() {
+            let oriented = data[0].oriented(orientation);
+            let identity = Orientation::IDENTITY;
+            assert_eq!(
+                data[0].edge_signatures(orientation),
+                oriented.edge_signatures(identity)
+            );
+        }
+    }
+
+    fn select_tiles(ids: &[usize]) -> String {
+        INP.split("\n\n")
+            .filter(|block| {
+                ids.iter()
+                    .any(|id| block.starts_with(&format!("Tile {id}:")))
+            })
+            .join("\n\n")
+    }
+
+    #[test]
+    fn test_rectangular_puzzle() {
+        let inp = select_tiles(&[1951, 2311, 3079, 2729, 1427, 2473]);
+        let Ok(data) = generate(&inp) else {
+            panic!("Could not parse test input")
+        };
+        assert_eq!(Ok((3, 2)), EdgeIndex::new(&data).image_dims());
+        assert_eq!(Ok(1951 * 3079 * 2729 * 2473), part1(&data));
+
+        let inp = select_tiles(&[1951, 2311]);
+        let Ok(data) = generate(&inp) else {
+            panic!("Could not parse test input")
+        };
+        assert_eq!(Ok(1951 * 2311), part1(&data));
+    }
+
+    // Cuts a pseudo-random 10 x (9 * len + 1) image into a row of `len` tiles,
+    // numbered from 2 onwards
+    fn strip_puzzle(len: usize) -> String {
+        let mut seed = 12_345_u64;
+        let rows = (0..10)
+            .map(|_| {
+                (0..=9 * len)
+                    .map(|_| {
+                        seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1);
+                        if seed >> 63 == 1 {
+                            '#'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect_vec()
+            })
+            .collect_vec();
+
+        (0..len)
+            .map(|idx| {
+                let tile = rows
+                    .iter()
+                    .map(|row| row[9 * idx..=9 * idx + 9].iter().collect::<String>())
+                    .join("\n");
+                format!("Tile {}:\n{tile}", idx + 2)
+            })
+            .join("\n\n")
+    }
+
+    #[test]
+    fn test_single_row_puzzle() {
+        let Ok(data) = generate(&strip_puzzle(6)) else {
+            panic!("Could not parse test input")
+        };
+        assert_eq!(Ok((6, 1)), EdgeIndex::new(&data).image_dims());
+        assert_eq!(4, EdgeIndex::new(&data).corner_tiles().len());
+        assert_eq!(Ok(2 * 7), part1(&data));
+    }
+
+    #[test]
+    fn test_invalid_puzzles() {
+        let inp = select_tiles(&[1951, 1171]);
+        let Ok(data) = generate(&inp) else {
+            panic!("Could not parse test input")
+        };
+        assert_eq!(
+            Err(PuzzleError::NotRectangular {
+                tiles: 2,
+                border_edges: 8
+            }),
+            part1(&data)
+        );
+
+        let ragged = "Tile 1:\n#.#\n.#\n#.#";
+        assert_eq!(
+            Some(PuzzleError::RaggedTile {
+                id: 1,
+                rows: 3,
+                cols: 2
+            }),
+            generate(ragged).err()
+        );
+
+        let mismatched = "Tile 1:\n#.#\n.#.\n#.#\n\nTile 2:\n#.\n.#";
+        assert_eq!(
+            Some(PuzzleError::MismatchedTileSize {
+                id: 2,
+                size: 2,
+                expected: 3
+            }),
+            generate(mismatched).err()
+        );
+    }
+
+    #[test]
+    fn test_search_sea_monsters() {
+        let Ok(data) = generate(INP) else {
+            panic!("Could not parse test input")
+        };
+        let Ok(matches) = search_image(&data, SEA_MONSTER) else {
+            panic!("Could not search image")
+        };
+
+        assert_eq!(2, matches.len());
+        assert!(matches
+            .iter()
+            .all(|it| it.orientation == matches[0].orientation));
+    }
+
+    #[test]
+    fn test_overlapping_pattern() {
+        let Some(map) = Grid::parse("#####", Some) else {
+            panic!("Could not parse test input")
+        };
+        let image = MapTile { id: 0, map };
+        let Ok(pattern) = "# #".parse::<Pattern>() else {
+            panic!("Could not parse pattern")
+        };
+
+        let identity = Orientation::IDENTITY;
+        let positions = find_pattern(&image, &pattern)
+            .into_iter()
+            .filter(|it| it.orientation == identity)
+            .map(|it| (it.row, it.col))
+            .collect_vec();
+        assert_eq!(vec![(0, 0), (0, 1), (0, 2)], positions);
+
+        assert_eq!(
+            Some(PuzzleError::InvalidPattern('.')),
+            "#.#".parse::<Pattern>().err()
+        );
+        assert_eq!(
+            Some(PuzzleError::EmptyPattern),
+            "   ".parse::<Pattern>().err()
+        );
+    }
+
+    #[test]
+    fn test_render_assembly() {
+        let Ok(data) = generate(INP) else {
+            panic!("Could not parse test input")
+        };
+        let Ok(rendered) = render_assembly(&data) else {
+            panic!("Could not assemble image")
+        };
+
+        let lines = rendered.lines().collect_vec();
+        assert_eq!(37, lines.len());
+        assert!(lines.iter().all(|it| it.len() == 34));
+        assert_eq!("+----------+----------+----------+", lines[0]);
+        assert_eq!(
+            vec![1171, 1951, 2971, 3079],
+            [lines[1], lines[25]]
+                .iter()
+                .flat_map(|it| it.split('|'))
+                .filter_map(|it| it.trim().parse::<usize>().ok())
+                .filter(|it| [1171, 1951, 2971, 3079].contains(it))
+                .sorted()
+                .collect_vec()
+        );
+    }
+
+    #[test]
+    fn test_export_bitmaps() {
+        let Ok(data) = generate(INP) else {
+            panic!("Could not parse test input")
+        };
+        let Ok(image) = highlight_sea_monsters(&data) else {
+            panic!("Could not find sea monsters")
+        };
+
+        let pbm = image.to_pbm();
+        assert!(pbm.starts_with("P1\n24 24\n"));
+        assert_eq!(2 + 24, pbm.lines().count());
+
+        let ppm = image.to_ppm();
+        assert!(ppm.starts_with("P3\n24 24\n255\n"));
+        assert_eq!(30, ppm.matches("220 40 40").count());
     }
 }
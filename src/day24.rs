@@ -1,111 +1,204 @@
+use std::collections::{HashMap, HashSet};
+
 use aoc_runner_derive::{aoc, aoc_generator};
-use itertools::iproduct;
+use itertools::Itertools;
+use parse_display::{Display as PDisplay, ParseError};
+
+use crate::hex::{Hex, HexPath};
 
 #[aoc_generator(day24)]
-pub fn generate(inp: &str) -> Vec<Vec<(i64, i64)>> {
-    inp.lines()
-        .map(|it| {
-            let mut dirs = Vec::new();
-
-            let mut iter = it.chars().peekable();
-
-            while let Some(next) = iter.next() {
-                let dir = match next {
-                    'e' => (-1, 0),
-                    'w' => (1, 0),
-                    's' if iter.peek() == Some(&'e') => (-1, 1),
-                    's' if iter.peek() == Some(&'w') => (0, 1),
-                    'n' if iter.peek() == Some(&'w') => (1, -1),
-                    'n' if iter.peek() == Some(&'e') => (0, -1),
-                    _ => panic!("Invalid char"),
-                };
+pub fn generate(inp: &str) -> Result<Vec<HexPath>, ParseError> {
+    inp.lines().map(str::parse).collect()
+}
 
-                if dir != (-1, 0) && dir != (1, 0) {
-                    iter.next();
-                }
+pub fn normalize(path: &HexPath) -> (Hex, HexPath) {
+    (path.destination(Hex::ORIGIN), path.normalized())
+}
 
-                dirs.push(dir);
-            }
+// A path back to the reference tile is an empty line. Every line gets a newline,
+// as `lines` would drop an empty one at the very end otherwise.
+pub fn normalize_input(inp: &str) -> Result<String, ParseError> {
+    Ok(generate(inp)?
+        .iter()
+        .map(|it| format!("{}\n", it.normalized()))
+        .collect())
+}
 
-            dirs
-        })
-        .collect()
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Floor {
+    black_tiles: HashSet<Hex>,
 }
 
-fn setup_tiles(insts: &[Vec<(i64, i64)>]) -> Vec<Vec<bool>> {
-    const LEN: usize = 500;
+impl Floor {
+    pub fn from_instructions(insts: &[HexPath]) -> Self {
+        let black_tiles = insts.iter().fold(HashSet::new(), |mut acc, it| {
+            let pos = it.destination(Hex::ORIGIN);
 
-    insts
-        .iter()
-        .fold(vec![vec![true; LEN]; LEN], |mut acc, it| {
-            let pos = it
+            if !acc.remove(&pos) {
+                acc.insert(pos);
+            }
+
+            acc
+        });
+
+        Self { black_tiles }
+    }
+
+    pub fn step(&self) -> Self {
+        let mut black_neighbors = HashMap::<Hex, usize>::new();
+
+        for tile in &self.black_tiles {
+            for neighbor in tile.neighbors() {
+                *black_neighbors.entry(neighbor).or_default() += 1;
+            }
+        }
+
+        let black_tiles = black_neighbors
+            .into_iter()
+            .filter(|(tile, count)| *count == 2 || (*count == 1 && self.black_tiles.contains(tile)))
+            .map(|(tile, _)| tile)
+            .collect();
+
+        Self { black_tiles }
+    }
+
+    pub fn count_black(&self) -> usize {
+        self.black_tiles.len()
+    }
+
+    pub fn is_black(&self, tile: Hex) -> bool {
+        self.black_tiles.contains(&tile)
+    }
+
+    // Distance from the reference tile to the farthest black tile
+    pub fn radius(&self) -> i64 {
+        self.black_tiles
+            .iter()
+            .map(|it| it.distance(Hex::ORIGIN))
+            .max()
+            .unwrap_or(0)
+    }
+
+    // Axial rows, each shifted half a tile to the right of the one above it
+    pub fn render_ascii(&self) -> String {
+        let (Some((q_min, q_max)), Some((r_min, r_max))) = (
+            self.black_tiles
+                .iter()
+                .map(|it| it.q)
+                .minmax()
+                .into_option(),
+            self.black_tiles
                 .iter()
-                .fold((LEN / 2, LEN / 2), |(acc_l, acc_r), (it_l, it_r)| {
-                    #[allow(clippy::cast_possible_wrap)]
-                    let new_x = (acc_l as i64 + it_l) as usize;
+                .map(|it| it.r)
+                .minmax()
+                .into_option(),
+        ) else {
+            return String::new();
+        };
 
-                    #[allow(clippy::cast_possible_wrap)]
-                    let new_y = (acc_r as i64 + it_r) as usize;
-                    (new_x, new_y)
-                });
+        (r_min..=r_max)
+            .map(|r| {
+                let cells = (q_min..=q_max)
+                    .map(|q| {
+                        if self.is_black(Hex::new(q, r)) {
+                            '#'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .join(" ");
+                format!("{}{cells}", " ".repeat((r - r_min) as usize))
+            })
+            .join("\n")
+    }
 
-            acc[pos.0][pos.1] = !acc[pos.0][pos.1];
-            acc
-        })
-}
+    #[allow(clippy::cast_precision_loss)]
+    pub fn to_svg(&self) -> String {
+        const SIZE: f64 = 10.0;
+        let sqrt3 = 3.0_f64.sqrt();
+        let radius = self.radius();
 
-#[aoc(day24, part1)]
-pub fn part1(insts: &[Vec<(i64, i64)>]) -> usize {
-    let tiles = setup_tiles(insts);
+        let tiles = (0..=radius)
+            .flat_map(|ring| Hex::ORIGIN.ring(ring))
+            .map(|tile| {
+                let x = SIZE * sqrt3 * (tile.q as f64 + tile.r as f64 / 2.0);
+                let y = SIZE * 1.5 * tile.r as f64;
 
-    tiles
-        .iter()
-        .fold(0, |acc, it| acc + it.iter().filter(|v| !**v).count())
+                let points = (0..6)
+                    .map(|corner| {
+                        let angle = f64::from(60 * corner - 30).to_radians();
+                        format!(
+                            "{:.2},{:.2}",
+                            SIZE.mul_add(angle.cos(), x),
+                            SIZE.mul_add(angle.sin(), y)
+                        )
+                    })
+                    .join(" ");
+                let fill = if self.is_black(tile) {
+                    "black"
+                } else {
+                    "white"
+                };
+
+                format!("<polygon points=\"{points}\" fill=\"{fill}\" stroke=\"gray\"/>")
+            })
+            .join("\n");
+
+        let half_width = SIZE * sqrt3 * (radius as f64 + 1.0);
+        let half_height = SIZE * (1.5 * radius as f64 + 1.0);
+
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{:.2} {:.2} {:.2} {:.2}\">\n{tiles}\n</svg>",
+            -half_width,
+            -half_height,
+            2.0 * half_width,
+            2.0 * half_height
+        )
+    }
 }
 
-fn get_neighbor_indices() -> Vec<(i64, i64)> {
-    vec![(0, -1), (1, -1), (1, 0), (0, 1), (-1, 1), (-1, 0)]
+#[derive(PDisplay, Copy, Clone, Debug, PartialEq, Eq)]
+#[display("Day {day}: {black_tiles} (radius {radius})")]
+pub struct DayStats {
+    pub day: usize,
+    pub black_tiles: usize,
+    pub radius: i64,
 }
 
-#[aoc(day24, part2)]
-pub fn part2(insts: &[Vec<(i64, i64)>]) -> usize {
-    let neighbors = get_neighbor_indices();
+pub fn daily_stats(insts: &[HexPath], days: usize) -> Vec<DayStats> {
+    let mut floor = Floor::from_instructions(insts);
+    let mut stats = Vec::new();
 
-    let mut cur_tiles = setup_tiles(insts);
+    for day in 0..=days {
+        stats.push(DayStats {
+            day,
+            black_tiles: floor.count_black(),
+            radius: floor.radius(),
+        });
 
-    for _ in 0..100 {
-        let mut next_tiles = cur_tiles.clone();
+        floor = floor.step();
+    }
 
-        for (x, y) in iproduct!(1..cur_tiles.len() - 1, 1..cur_tiles.len() - 1) {
-            let cur = cur_tiles[x][y];
+    stats
+}
 
-            let black_neighbors = neighbors
-                .iter()
-                .filter(|(l, r)| {
-                    #[allow(clippy::cast_possible_wrap)]
-                    let dx = (x as i64 + l) as usize;
-
-                    #[allow(clippy::cast_possible_wrap)]
-                    let dy = (y as i64 + r) as usize;
-                    !cur_tiles[dx][dy]
-                })
-                .count();
-
-            let next_state = if cur {
-                black_neighbors != 2
-            } else {
-                black_neighbors == 0 || black_neighbors > 2
-            };
-
-            next_tiles[x][y] = next_state;
-        }
+#[aoc(day24, part1)]
+pub fn part1(insts: &[HexPath]) -> usize {
+    Floor::from_instructions(insts).count_black()
+}
 
-        cur_tiles = next_tiles;
+pub fn run_days(insts: &[HexPath], days: usize) -> usize {
+    let mut floor = Floor::from_instructions(insts);
+    for _ in 0..days {
+        floor = floor.step();
     }
 
-    cur_tiles
-        .iter()
-        .fold(0, |acc, it| acc + it.iter().filter(|v| !**v).count())
+    floor.count_black()
+}
+
+#[aoc(day24, part2)]
+pub fn part2(insts: &[HexPath]) -> usize {
+    run_days(insts, 100)
 }
 
 #[cfg(test)]
@@ -135,13 +228,101 @@ wseweeenwnesenwwwswnew";
 
     #[test]
     fn test_part1() {
-        let insts = generate(INP);
+        let Ok(insts) = generate(INP) else {
+            panic!("Could not parse test input")
+        };
         assert_eq!(10, part1(&insts));
     }
 
     #[test]
     fn test_part2() {
-        let insts = generate(INP);
+        let Ok(insts) = generate(INP) else {
+            panic!("Could not parse test input")
+        };
         assert_eq!(2208, part2(&insts));
     }
+
+    #[test]
+    fn test_days() {
+        let Ok(insts) = generate(INP) else {
+            panic!("Could not parse test input")
+        };
+        for (days, expected) in &[(1, 15), (2, 12), (10, 37), (20, 132), (50, 566)] {
+            assert_eq!(*expected, run_days(&insts, *days));
+        }
+    }
+
+    #[test]
+    fn test_long_paths() {
+        let far_east = "e".repeat(10_000);
+        let Ok(insts) = generate(&format!("{far_east}\n{far_east}w\nnw")) else {
+            panic!("Could not parse test input")
+        };
+        assert_eq!(3, part1(&insts));
+        assert_eq!(4, run_days(&insts, 1));
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        assert!(generate("esew\nnex").is_err());
+    }
+
+    #[test]
+    fn test_render() {
+        let Ok(insts) = generate("esew\nnwwswee\ne") else {
+            panic!("Could not parse test input")
+        };
+        let floor = Floor::from_instructions(&insts);
+        assert_eq!(1, floor.radius());
+        assert_eq!("# #\n # .", floor.render_ascii());
+
+        let svg = floor.to_svg();
+        assert!(svg.starts_with("<svg "));
+        assert_eq!(7, svg.matches("<polygon").count());
+        assert_eq!(3, svg.matches("fill=\"black\"").count());
+
+        assert_eq!("", Floor::default().render_ascii());
+    }
+
+    #[test]
+    fn test_daily_stats() {
+        let Ok(insts) = generate(INP) else {
+            panic!("Could not parse test input")
+        };
+        let stats = daily_stats(&insts, 10);
+        assert_eq!(11, stats.len());
+        assert_eq!(10, stats[0].black_tiles);
+        assert_eq!(37, stats[10].black_tiles);
+        assert_eq!("Day 1: 15 (radius 4)", stats[1].to_string());
+        assert!(stats.windows(2).all(|it| it[1].radius <= it[0].radius + 1));
+    }
+
+    #[test]
+    fn test_normalize() {
+        let Ok(path) = "nwwswee".parse() else {
+            panic!("Could not parse test input")
+        };
+        assert_eq!((Hex::ORIGIN, HexPath::default()), normalize(&path));
+
+        let Ok(minimal) = normalize_input(INP) else {
+            panic!("Could not parse test input")
+        };
+        assert!(minimal.len() < INP.len());
+
+        let Ok(insts) = generate(&minimal) else {
+            panic!("Could not parse test input")
+        };
+        assert_eq!(10, part1(&insts));
+        assert_eq!(37, run_days(&insts, 10));
+        assert_eq!(Ok(minimal.clone()), normalize_input(&minimal));
+
+        let Ok(minimal) = normalize_input("e\nnwwswee") else {
+            panic!("Could not parse test input")
+        };
+        assert_eq!("e\n\n", minimal);
+        let Ok(insts) = generate(&minimal) else {
+            panic!("Could not parse test input")
+        };
+        assert_eq!(2, part1(&insts));
+    }
 }
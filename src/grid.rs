@@ -0,0 +1,288 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::{Index, IndexMut};
+
+use itertools::{iproduct, Itertools};
+
+pub type Pos = (usize, usize);
+
+pub const ADJACENT: &[(i64, i64); 8] = &[
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Orientation {
+    flipped: bool,
+    rotations: usize,
+}
+
+impl Orientation {
+    pub const IDENTITY: Self = Self {
+        flipped: false,
+        rotations: 0,
+    };
+
+    pub fn all() -> impl Iterator<Item = Self> {
+        iproduct!([false, true], 0..4).map(|(flipped, rotations)| Self { flipped, rotations })
+    }
+
+    const fn dims(self, height: usize, width: usize) -> (usize, usize) {
+        if self.rotations.is_multiple_of(2) {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    // Maps a position in the oriented grid back to the source grid. The source is
+    // flipped along the x axis first and then rotated clockwise `rotations` times.
+    fn source_pos(self, height: usize, width: usize, (row, col): Pos) -> Pos {
+        let (mut row, mut col) = (row, col);
+
+        for step in (0..self.rotations).rev() {
+            let (cur_height, _) = Self {
+                rotations: step,
+                ..self
+            }
+            .dims(height, width);
+            (row, col) = (cur_height - 1 - col, row);
+        }
+
+        if self.flipped {
+            col = width - 1 - col;
+        }
+
+        (row, col)
+    }
+}
+
+#[derive(Default, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T> Grid<T> {
+    pub fn from_rows(rows: Vec<Vec<T>>) -> Option<Self> {
+        let height = rows.len();
+        let width = rows.first().map_or(0, Vec::len);
+
+        if rows.iter().any(|it| it.len() != width) {
+            return None;
+        }
+
+        Some(Self {
+            width,
+            height,
+            cells: rows.into_iter().flatten().collect(),
+        })
+    }
+
+    pub fn parse(inp: &str, mut parse_cell: impl FnMut(char) -> Option<T>) -> Option<Self> {
+        let rows = inp
+            .lines()
+            .map(|l| l.chars().map(&mut parse_cell).collect::<Option<Vec<_>>>())
+            .collect::<Option<Vec<_>>>()?;
+
+        Self::from_rows(rows)
+    }
+
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, (row, col): Pos) -> Option<&T> {
+        (row < self.height && col < self.width).then(|| &self.cells[row * self.width + col])
+    }
+
+    pub fn get_mut(&mut self, (row, col): Pos) -> Option<&mut T> {
+        let width = self.width;
+        (row < self.height && col < width).then(move || &mut self.cells[row * width + col])
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = Pos> {
+        let (height, width) = (self.height, self.width);
+        iproduct!(0..height, 0..width)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Pos, &T)> {
+        self.positions().zip(&self.cells)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.cells.iter()
+    }
+
+    pub fn offset(&self, (row, col): Pos, (row_dir, col_dir): (i64, i64)) -> Option<Pos> {
+        let row = usize::try_from(i64::try_from(row).ok()? + row_dir).ok()?;
+        let col = usize::try_from(i64::try_from(col).ok()? + col_dir).ok()?;
+
+        (row < self.height && col < self.width).then_some((row, col))
+    }
+
+    pub fn neighbors(&self, pos: Pos) -> impl Iterator<Item = Pos> + '_ {
+        ADJACENT.iter().filter_map(move |it| self.offset(pos, *it))
+    }
+
+    pub fn row(&self, row: usize) -> &[T] {
+        &self.cells[row * self.width..(row + 1) * self.width]
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        self.cells.chunks(self.width.max(1))
+    }
+
+    pub fn column(&self, col: usize) -> impl Iterator<Item = &T> {
+        self.cells.iter().skip(col).step_by(self.width.max(1))
+    }
+
+    pub fn top_edge(&self) -> impl Iterator<Item = &T> {
+        self.row(0).iter()
+    }
+
+    pub fn bottom_edge(&self) -> impl Iterator<Item = &T> {
+        self.row(self.height.saturating_sub(1)).iter()
+    }
+
+    pub fn left_edge(&self) -> impl Iterator<Item = &T> {
+        self.column(0)
+    }
+
+    pub fn right_edge(&self) -> impl Iterator<Item = &T> {
+        self.column(self.width.saturating_sub(1))
+    }
+
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> Grid<U> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+
+    pub fn get_oriented(&self, orientation: Orientation, pos: Pos) -> &T {
+        &self[orientation.source_pos(self.height, self.width, pos)]
+    }
+
+    pub const fn oriented_dims(&self, orientation: Orientation) -> (usize, usize) {
+        orientation.dims(self.height, self.width)
+    }
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn new(width: usize, height: usize, value: T) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![value; width * height],
+        }
+    }
+
+    pub fn oriented(&self, orientation: Orientation) -> Self {
+        let (height, width) = self.oriented_dims(orientation);
+
+        Self {
+            width,
+            height,
+            cells: iproduct!(0..height, 0..width)
+                .map(|pos| self.get_oriented(orientation, pos).clone())
+                .collect(),
+        }
+    }
+
+    pub fn rotate_clockwise(&self) -> Self {
+        self.oriented(Orientation {
+            flipped: false,
+            rotations: 1,
+        })
+    }
+
+    pub fn flip_horizontal(&self) -> Self {
+        self.oriented(Orientation {
+            flipped: true,
+            rotations: 0,
+        })
+    }
+}
+
+impl<T> Index<Pos> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, (row, col): Pos) -> &Self::Output {
+        assert!(
+            row < self.height && col < self.width,
+            "Position out of bounds"
+        );
+        &self.cells[row * self.width + col]
+    }
+}
+
+impl<T> IndexMut<Pos> for Grid<T> {
+    fn index_mut(&mut self, (row, col): Pos) -> &mut Self::Output {
+        assert!(
+            row < self.height && col < self.width,
+            "Position out of bounds"
+        );
+        &mut self.cells[row * self.width + col]
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Grid<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows = self.rows().map(|it| it.iter().join(""));
+        write!(f, "{}", rows.format("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Grid<char> {
+        let Some(grid) = Grid::parse("ab\ncd\nef", Some) else {
+            panic!("Could not parse test input")
+        };
+        grid
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        let grid = sample();
+        assert_eq!((2, 3), (grid.width(), grid.height()));
+        assert_eq!("ab\ncd\nef", grid.to_string());
+        assert_eq!(None, Grid::parse("ab\nc", Some));
+    }
+
+    #[test]
+    fn test_orientations() {
+        let grid = sample();
+        assert_eq!("eca\nfdb", grid.rotate_clockwise().to_string());
+        assert_eq!("ba\ndc\nfe", grid.flip_horizontal().to_string());
+
+        let all = Orientation::all().map(|it| grid.oriented(it)).collect_vec();
+        assert_eq!(8, all.iter().unique().count());
+    }
+
+    #[test]
+    fn test_neighbors_and_edges() {
+        let grid = sample();
+        assert_eq!(3, grid.neighbors((0, 0)).count());
+        assert_eq!(5, grid.neighbors((1, 0)).count());
+        assert_eq!("ace", grid.left_edge().collect::<String>());
+        assert_eq!("bdf", grid.right_edge().collect::<String>());
+        assert_eq!("ef", grid.bottom_edge().collect::<String>());
+        assert_eq!(None, grid.offset((0, 1), (0, 1)));
+    }
+}
@@ -0,0 +1,346 @@
+use std::fmt;
+use std::ops::{Add, Mul, Sub};
+use std::str::FromStr;
+
+use itertools::Itertools;
+use parse_display::{Display as PDisplay, FromStr as PFromStr, ParseError};
+
+#[derive(PDisplay, PFromStr, Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum HexDir {
+    #[display("e")]
+    East,
+
+    #[display("se")]
+    SouthEast,
+
+    #[display("sw")]
+    SouthWest,
+
+    #[display("w")]
+    West,
+
+    #[display("nw")]
+    NorthWest,
+
+    #[display("ne")]
+    NorthEast,
+}
+
+impl HexDir {
+    // Clockwise, starting east
+    pub const ALL: [Self; 6] = [
+        Self::East,
+        Self::SouthEast,
+        Self::SouthWest,
+        Self::West,
+        Self::NorthWest,
+        Self::NorthEast,
+    ];
+
+    pub const fn offset(self) -> Hex {
+        match self {
+            Self::East => Hex::new(1, 0),
+            Self::SouthEast => Hex::new(0, 1),
+            Self::SouthWest => Hex::new(-1, 1),
+            Self::West => Hex::new(-1, 0),
+            Self::NorthWest => Hex::new(0, -1),
+            Self::NorthEast => Hex::new(1, -1),
+        }
+    }
+}
+
+// Axial coordinates of a pointy-top hexagon: `q` grows to the east, `r` to the south-east.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Hex {
+    pub q: i64,
+    pub r: i64,
+}
+
+impl Hex {
+    pub const ORIGIN: Self = Self::new(0, 0);
+
+    pub const fn new(q: i64, r: i64) -> Self {
+        Self { q, r }
+    }
+
+    pub const fn to_cube(self) -> (i64, i64, i64) {
+        (self.q, -self.q - self.r, self.r)
+    }
+
+    pub const fn from_cube((x, _, z): (i64, i64, i64)) -> Self {
+        Self::new(x, z)
+    }
+
+    // "odd-r" offset coordinates, every odd row is shoved half a tile to the east
+    pub const fn to_offset(self) -> (i64, i64) {
+        let col = self.q + (self.r - (self.r & 1)) / 2;
+        (col, self.r)
+    }
+
+    pub const fn from_offset((col, row): (i64, i64)) -> Self {
+        Self::new(col - (row - (row & 1)) / 2, row)
+    }
+
+    pub fn neighbor(self, dir: HexDir) -> Self {
+        self + dir.offset()
+    }
+
+    pub fn neighbors(self) -> impl Iterator<Item = Self> {
+        HexDir::ALL.iter().map(move |dir| self.neighbor(*dir))
+    }
+
+    pub const fn distance(self, other: Self) -> i64 {
+        let dq = self.q - other.q;
+        let dr = self.r - other.r;
+        (dq.abs() + dr.abs() + (dq + dr).abs()) / 2
+    }
+
+    // A shortest path never needs more than two adjacent directions. The steps are
+    // emitted in `HexDir::ALL` order so that every destination has a single spelling.
+    pub fn path_to(self, other: Self) -> HexPath {
+        let delta = other - self;
+
+        let mut dirs = HexDir::ALL
+            .iter()
+            .zip(HexDir::ALL.iter().cycle().skip(1))
+            .find_map(|(first, second)| {
+                let (a, b) = (first.offset(), second.offset());
+                let det = a.q * b.r - a.r * b.q;
+                let first_steps = (delta.q * b.r - delta.r * b.q) / det;
+                let second_steps = (a.q * delta.r - a.r * delta.q) / det;
+
+                (first_steps >= 0 && second_steps >= 0).then(|| {
+                    std::iter::repeat_n(*first, first_steps as usize)
+                        .chain(std::iter::repeat_n(*second, second_steps as usize))
+                        .collect_vec()
+                })
+            })
+            .unwrap_or_default();
+        dirs.sort_unstable();
+
+        HexPath(dirs)
+    }
+
+    pub fn ring(self, radius: i64) -> Vec<Self> {
+        if radius <= 0 {
+            return vec![self];
+        }
+
+        let mut cur = self + HexDir::NorthWest.offset() * radius;
+        let mut res = Vec::new();
+
+        for dir in &HexDir::ALL {
+            for _ in 0..radius {
+                res.push(cur);
+                cur = cur.neighbor(*dir);
+            }
+        }
+
+        res
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    pub fn line_to(self, other: Self) -> Vec<Self> {
+        let steps = self.distance(other);
+        if steps == 0 {
+            return vec![self];
+        }
+
+        let (ax, ay, az) = self.to_cube();
+        let (bx, by, bz) = other.to_cube();
+
+        // Nudge the samples off the tile edges so that ties always round the same way
+        let lerp =
+            |a: i64, b: i64, t: f64, nudge: f64| ((b - a) as f64).mul_add(t, a as f64) + nudge;
+
+        (0..=steps)
+            .map(|step| {
+                let t = step as f64 / steps as f64;
+                cube_round((
+                    lerp(ax, bx, t, 1e-6),
+                    lerp(ay, by, t, 2e-6),
+                    lerp(az, bz, t, -3e-6),
+                ))
+            })
+            .collect()
+    }
+}
+
+fn cube_round((x, y, z): (f64, f64, f64)) -> Hex {
+    let (rx, ry, rz) = (x.round(), y.round(), z.round());
+    let (dx, dy, dz) = ((rx - x).abs(), (ry - y).abs(), (rz - z).abs());
+
+    let (rx, rz) = if dx > dy && dx > dz {
+        (-ry - rz, rz)
+    } else if dy > dz {
+        (rx, rz)
+    } else {
+        (rx, -rx - ry)
+    };
+
+    Hex::new(rx as i64, rz as i64)
+}
+
+impl Add for Hex {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::new(self.q + rhs.q, self.r + rhs.r)
+    }
+}
+
+impl Sub for Hex {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::new(self.q - rhs.q, self.r - rhs.r)
+    }
+}
+
+impl Mul<i64> for Hex {
+    type Output = Self;
+
+    fn mul(self, rhs: i64) -> Self::Output {
+        Self::new(self.q * rhs, self.r * rhs)
+    }
+}
+
+impl fmt::Display for Hex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}, {})", self.q, self.r)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HexPath(pub Vec<HexDir>);
+
+impl HexPath {
+    pub fn destination(&self, start: Hex) -> Hex {
+        self.0.iter().fold(start, |acc, dir| acc.neighbor(*dir))
+    }
+
+    pub fn normalized(&self) -> Self {
+        Hex::ORIGIN.path_to(self.destination(Hex::ORIGIN))
+    }
+}
+
+impl FromStr for HexPath {
+    type Err = ParseError;
+
+    fn from_str(inp: &str) -> Result<Self, Self::Err> {
+        let mut dirs = Vec::new();
+        let mut rest = inp.trim();
+
+        while !rest.is_empty() {
+            let len = if rest.starts_with('n') || rest.starts_with('s') {
+                2
+            } else {
+                1
+            };
+
+            let dir = rest
+                .get(..len)
+                .and_then(|it| it.parse::<HexDir>().ok())
+                .ok_or_else(|| ParseError::with_message("Invalid hex direction"))?;
+
+            dirs.push(dir);
+            rest = &rest[len..];
+        }
+
+        Ok(Self(dirs))
+    }
+}
+
+impl fmt::Display for HexPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.iter().format(""))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_path() {
+        let Ok(path) = "nwwswee".parse::<HexPath>() else {
+            panic!("Could not parse test input")
+        };
+        assert_eq!(5, path.0.len());
+        assert_eq!("nwwswee", path.to_string());
+        assert_eq!(Hex::ORIGIN, path.destination(Hex::ORIGIN));
+
+        let Ok(path) = "esew".parse::<HexPath>() else {
+            panic!("Could not parse test input")
+        };
+        assert_eq!(
+            Hex::ORIGIN.neighbor(HexDir::SouthEast),
+            path.destination(Hex::ORIGIN)
+        );
+
+        assert!("nx".parse::<HexPath>().is_err());
+        assert!("n".parse::<HexPath>().is_err());
+    }
+
+    #[test]
+    fn test_conversions() {
+        for hex in Hex::ORIGIN
+            .ring(3)
+            .into_iter()
+            .chain(Hex::new(-2, 5).ring(2))
+        {
+            assert_eq!(hex, Hex::from_cube(hex.to_cube()));
+            assert_eq!(hex, Hex::from_offset(hex.to_offset()));
+
+            let (x, y, z) = hex.to_cube();
+            assert_eq!(0, x + y + z);
+        }
+
+        assert_eq!((0, 1), Hex::new(0, 1).to_offset());
+        assert_eq!((-1, 1), Hex::new(-1, 1).to_offset());
+    }
+
+    #[test]
+    fn test_distance_ring_and_line() {
+        let center = Hex::new(1, -2);
+
+        assert_eq!(vec![center], center.ring(0));
+        for radius in 1..5 {
+            let ring = center.ring(radius);
+            assert_eq!(6 * radius as usize, ring.iter().unique().count());
+            assert!(ring.iter().all(|it| it.distance(center) == radius));
+        }
+
+        let target = Hex::new(4, 3);
+        let line = center.line_to(target);
+        assert_eq!(center.distance(target) as usize + 1, line.len());
+        assert_eq!(Some(&center), line.first());
+        assert_eq!(Some(&target), line.last());
+        assert!(line.windows(2).all(|it| it[0].distance(it[1]) == 1));
+    }
+
+    #[test]
+    fn test_shortest_path() {
+        let center = Hex::new(-3, 2);
+
+        for radius in 0..6 {
+            for target in center.ring(radius) {
+                let path = center.path_to(target);
+                assert_eq!(radius as usize, path.0.len());
+                assert_eq!(target, path.destination(center));
+            }
+        }
+
+        for (inp, expected) in &[
+            ("nwwswee", ""),
+            ("esew", "se"),
+            ("nee", "ene"),
+            ("wwsw", "swww"),
+        ] {
+            let Ok(path) = inp.parse::<HexPath>() else {
+                panic!("Could not parse test input")
+            };
+            assert_eq!(*expected, path.normalized().to_string());
+        }
+    }
+}
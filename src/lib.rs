@@ -7,7 +7,9 @@
     clippy::cast_sign_loss
 )]
 
-mod ship;
+pub mod grid;
+pub mod hex;
+pub mod ship;
 
 mod day01;
 mod day02;
@@ -19,20 +21,20 @@ mod day07;
 mod day08;
 mod day09;
 mod day10;
-mod day11;
-mod day12;
-mod day13;
+pub mod day11;
+pub mod day12;
+pub mod day13;
 mod day14;
 mod day15;
 mod day16;
-mod day17;
+pub mod day17;
 mod day18;
 mod day19;
-mod day20;
+pub mod day20;
 mod day21;
 mod day22;
 mod day23;
-mod day24;
+pub mod day24;
 mod day25;
 
 aoc_runner_derive::aoc_lib! { year = 2020 }
@@ -1,7 +1,8 @@
+use itertools::Itertools;
 use num_complex::Complex;
 use parse_display::{Display as PDisplay, FromStr as PFromStr};
 
-#[derive(PDisplay, PFromStr, Copy, Clone)]
+#[derive(PDisplay, PFromStr, Copy, Clone, Debug, PartialEq, Eq)]
 pub enum NavigationAction {
     #[display("N{0}")]
     North(i64),
@@ -25,9 +26,47 @@ pub enum NavigationAction {
     Forward(i64),
 }
 
+#[derive(PDisplay, Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NavigationError {
+    #[display("Cannot perform {0}, only multiples of 90 degrees are supported")]
+    UnsupportedTurn(NavigationAction),
+
+    #[display("Need {needed} fuel but only {remaining} is left")]
+    OutOfFuel { needed: i64, remaining: i64 },
+
+    #[display("Cannot move {distance} in one action, the speed limit is {limit}")]
+    TooFast { distance: i64, limit: i64 },
+}
+
+impl std::error::Error for NavigationError {}
+
 pub trait Ship {
-    fn perform_action(&mut self, action: &NavigationAction);
+    fn perform_action(&mut self, action: &NavigationAction) -> Result<(), NavigationError>;
     fn get_distance(&self) -> i64;
+    fn position(&self) -> Complex<i64>;
+    fn reset(&mut self);
+
+    // Degrees counter-clockwise from east, for ships that steer by heading
+    fn heading(&self) -> Option<f64> {
+        None
+    }
+
+    fn waypoint(&self) -> Option<Complex<i64>> {
+        None
+    }
+}
+
+fn degrees(direction: Complex<f64>) -> f64 {
+    direction.arg().to_degrees().rem_euclid(360.0)
+}
+
+// Counter-clockwise quarter turns, any number of full turns is allowed
+fn quarter_turns(degrees: i64) -> Option<u32> {
+    (degrees % 90 == 0).then(|| (degrees / 90).rem_euclid(4) as u32)
+}
+
+fn rotate(vec: Complex<i64>, degrees: i64) -> Option<Complex<i64>> {
+    Some(vec * Complex::i().powu(quarter_turns(degrees)?))
 }
 
 #[derive(Copy, Clone)]
@@ -44,16 +83,9 @@ impl Simple {
         }
     }
 
-    fn turn_left(&mut self, steps: i64) {
-        for _ in 0..steps {
-            self.direction *= Complex::new(0, 1);
-        }
-    }
-
-    fn turn_right(&mut self, steps: i64) {
-        for _ in 0..steps {
-            self.direction *= Complex::new(0, -1);
-        }
+    fn turn(&mut self, degrees: i64) -> Option<()> {
+        self.direction = rotate(self.direction, degrees)?;
+        Some(())
     }
 
     fn move_forward(&mut self, steps: i64) {
@@ -61,22 +93,48 @@ impl Simple {
     }
 }
 
+impl Default for Simple {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Ship for Simple {
-    fn perform_action(&mut self, action: &NavigationAction) {
+    fn perform_action(&mut self, action: &NavigationAction) -> Result<(), NavigationError> {
+        let unsupported = NavigationError::UnsupportedTurn(*action);
+
         match *action {
             NavigationAction::North(n) => self.position.im += n,
             NavigationAction::South(n) => self.position.im -= n,
             NavigationAction::East(n) => self.position.re += n,
             NavigationAction::West(n) => self.position.re -= n,
-            NavigationAction::Left(n) => self.turn_left(n / 90),
-            NavigationAction::Right(n) => self.turn_right(n / 90),
+            NavigationAction::Left(n) => self.turn(n).ok_or(unsupported)?,
+            NavigationAction::Right(n) => self.turn(-n).ok_or(unsupported)?,
             NavigationAction::Forward(n) => self.move_forward(n),
         };
+
+        Ok(())
     }
 
     fn get_distance(&self) -> i64 {
         self.position.l1_norm()
     }
+
+    fn position(&self) -> Complex<i64> {
+        self.position
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn heading(&self) -> Option<f64> {
+        Some(degrees(Complex::new(
+            self.direction.re as f64,
+            self.direction.im as f64,
+        )))
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -91,21 +149,9 @@ pub struct WithWaypoint {
 }
 
 impl Waypoint {
-    #[allow(clippy::cast_precision_loss)]
-    fn rotate(&mut self, degrees: i64, around: &Complex<i64>) {
-        let cur_pos = self.position - around;
-
-        let (s, c) = (degrees as f64).to_radians().sin_cos();
-
-        let cur_x = cur_pos.re as f64;
-        let cur_y = cur_pos.im as f64;
-
-        let new_x = c.mul_add(cur_x, -(s * cur_y)).round() as i64;
-        let new_y = s.mul_add(cur_x, c * cur_y).round() as i64;
-
-        let new_pos = Complex::new(new_x, new_y);
-
-        self.position = new_pos + around;
+    fn rotate(&mut self, degrees: i64, around: &Complex<i64>) -> Option<()> {
+        self.position = rotate(self.position - around, degrees)? + around;
+        Some(())
     }
 }
 
@@ -129,20 +175,568 @@ impl WithWaypoint {
     }
 }
 
+impl Default for WithWaypoint {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Ship for WithWaypoint {
-    fn perform_action(&mut self, action: &NavigationAction) {
+    fn perform_action(&mut self, action: &NavigationAction) -> Result<(), NavigationError> {
+        let unsupported = NavigationError::UnsupportedTurn(*action);
+
         match *action {
             NavigationAction::North(n) => self.waypoint.position.im += n,
             NavigationAction::South(n) => self.waypoint.position.im -= n,
             NavigationAction::East(n) => self.waypoint.position.re += n,
             NavigationAction::West(n) => self.waypoint.position.re -= n,
-            NavigationAction::Left(n) => self.waypoint.rotate(n, &self.position),
-            NavigationAction::Right(n) => self.waypoint.rotate(-n, &self.position),
+            NavigationAction::Left(n) => {
+                self.waypoint.rotate(n, &self.position).ok_or(unsupported)?
+            }
+            NavigationAction::Right(n) => self
+                .waypoint
+                .rotate(-n, &self.position)
+                .ok_or(unsupported)?,
             NavigationAction::Forward(n) => self.move_forward(n),
         };
+
+        Ok(())
     }
 
     fn get_distance(&self) -> i64 {
         self.position.l1_norm()
     }
+
+    fn position(&self) -> Complex<i64> {
+        self.position
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    fn waypoint(&self) -> Option<Complex<i64>> {
+        Some(self.waypoint.position)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Steering {
+    Heading,
+    Waypoint,
+}
+
+// Floating point ship that accepts any turning angle. With `Steering::Heading` it
+// behaves like `Simple`, with `Steering::Waypoint` like `WithWaypoint`; `direction`
+// is the unit heading or the waypoint offset from the ship respectively.
+#[derive(Copy, Clone)]
+pub struct FreeRotation {
+    steering: Steering,
+    position: Complex<f64>,
+    direction: Complex<f64>,
+}
+
+impl FreeRotation {
+    pub const fn new(steering: Steering) -> Self {
+        let direction = match steering {
+            Steering::Heading => Complex::new(1.0, 0.0),
+            Steering::Waypoint => Complex::new(10.0, 1.0),
+        };
+
+        Self {
+            steering,
+            position: Complex::new(0.0, 0.0),
+            direction,
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn shift(&mut self, offset: Complex<i64>) {
+        let offset = Complex::new(offset.re as f64, offset.im as f64);
+
+        match self.steering {
+            Steering::Heading => self.position += offset,
+            Steering::Waypoint => self.direction += offset,
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn turn(&mut self, degrees: i64) {
+        self.direction *= Complex::from_polar(1.0, (degrees as f64).to_radians());
+    }
+}
+
+impl Ship for FreeRotation {
+    #[allow(clippy::cast_precision_loss)]
+    fn perform_action(&mut self, action: &NavigationAction) -> Result<(), NavigationError> {
+        match *action {
+            NavigationAction::North(n) => self.shift(Complex::new(0, n)),
+            NavigationAction::South(n) => self.shift(Complex::new(0, -n)),
+            NavigationAction::East(n) => self.shift(Complex::new(n, 0)),
+            NavigationAction::West(n) => self.shift(Complex::new(-n, 0)),
+            NavigationAction::Left(n) => self.turn(n),
+            NavigationAction::Right(n) => self.turn(-n),
+            NavigationAction::Forward(n) => self.position += self.direction.scale(n as f64),
+        };
+
+        Ok(())
+    }
+
+    fn get_distance(&self) -> i64 {
+        self.position.l1_norm().round() as i64
+    }
+
+    fn position(&self) -> Complex<i64> {
+        round(self.position)
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new(self.steering);
+    }
+
+    fn heading(&self) -> Option<f64> {
+        (self.steering == Steering::Heading).then(|| degrees(self.direction))
+    }
+
+    fn waypoint(&self) -> Option<Complex<i64>> {
+        (self.steering == Steering::Waypoint).then(|| round(self.position + self.direction))
+    }
+}
+
+fn round(pos: Complex<f64>) -> Complex<i64> {
+    Complex::new(pos.re.round() as i64, pos.im.round() as i64)
+}
+
+// Every unit of distance the ship covers burns one unit of fuel, and a single action
+// may not move it further than `max_speed`. Actions that break either limit fail
+// without changing the ship.
+#[derive(Copy, Clone)]
+pub struct FuelLimited<T> {
+    ship: T,
+    capacity: i64,
+    fuel: i64,
+    max_speed: Option<i64>,
+}
+
+impl<T: Ship + Clone> FuelLimited<T> {
+    pub const fn new(ship: T, capacity: i64) -> Self {
+        Self {
+            ship,
+            capacity,
+            fuel: capacity,
+            max_speed: None,
+        }
+    }
+
+    pub fn with_max_speed(mut self, max_speed: i64) -> Self {
+        self.max_speed = Some(max_speed);
+        self
+    }
+
+    pub const fn fuel(&self) -> i64 {
+        self.fuel
+    }
+}
+
+impl<T: Ship + Clone> Ship for FuelLimited<T> {
+    fn perform_action(&mut self, action: &NavigationAction) -> Result<(), NavigationError> {
+        let mut next = self.ship.clone();
+        next.perform_action(action)?;
+
+        let distance = (next.position() - self.ship.position()).l1_norm();
+
+        if let Some(limit) = self.max_speed.filter(|it| distance > *it) {
+            return Err(NavigationError::TooFast { distance, limit });
+        }
+
+        if distance > self.fuel {
+            return Err(NavigationError::OutOfFuel {
+                needed: distance,
+                remaining: self.fuel,
+            });
+        }
+
+        self.fuel -= distance;
+        self.ship = next;
+
+        Ok(())
+    }
+
+    fn get_distance(&self) -> i64 {
+        self.ship.get_distance()
+    }
+
+    fn position(&self) -> Complex<i64> {
+        self.ship.position()
+    }
+
+    fn reset(&mut self) {
+        self.ship.reset();
+        self.fuel = self.capacity;
+    }
+
+    fn heading(&self) -> Option<f64> {
+        self.ship.heading()
+    }
+
+    fn waypoint(&self) -> Option<Complex<i64>> {
+        self.ship.waypoint()
+    }
+}
+
+// A steady current pushes the ship by `current` for every unit of distance it
+// covers. The waypoint, if any, drifts along with the ship.
+#[derive(Copy, Clone)]
+pub struct Drifting<T> {
+    ship: T,
+    current: Complex<i64>,
+    drift: Complex<i64>,
+}
+
+impl<T: Ship> Drifting<T> {
+    pub const fn new(ship: T, current: Complex<i64>) -> Self {
+        Self {
+            ship,
+            current,
+            drift: Complex::new(0, 0),
+        }
+    }
+}
+
+impl<T: Ship> Ship for Drifting<T> {
+    fn perform_action(&mut self, action: &NavigationAction) -> Result<(), NavigationError> {
+        let before = self.ship.position();
+        self.ship.perform_action(action)?;
+
+        let distance = (self.ship.position() - before).l1_norm();
+        self.drift += self.current.scale(distance);
+
+        Ok(())
+    }
+
+    fn get_distance(&self) -> i64 {
+        self.position().l1_norm()
+    }
+
+    fn position(&self) -> Complex<i64> {
+        self.ship.position() + self.drift
+    }
+
+    fn reset(&mut self) {
+        self.ship.reset();
+        self.drift = Complex::new(0, 0);
+    }
+
+    fn heading(&self) -> Option<f64> {
+        self.ship.heading()
+    }
+
+    fn waypoint(&self) -> Option<Complex<i64>> {
+        self.ship.waypoint().map(|it| it + self.drift)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TrajectoryPoint {
+    pub position: Complex<i64>,
+    pub waypoint: Option<Complex<i64>>,
+}
+
+// Ship state before the first action and after every following one
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Trajectory {
+    points: Vec<TrajectoryPoint>,
+}
+
+impl Trajectory {
+    pub fn record<T: Ship>(
+        ship: &mut T,
+        actions: &[NavigationAction],
+    ) -> Result<Self, NavigationError> {
+        let mut points = vec![TrajectoryPoint::of(ship)];
+
+        for action in actions {
+            ship.perform_action(action)?;
+            points.push(TrajectoryPoint::of(ship));
+        }
+
+        Ok(Self { points })
+    }
+
+    pub fn points(&self) -> &[TrajectoryPoint] {
+        &self.points
+    }
+
+    // South-west and north-east corners of the ship positions
+    pub fn bounding_box(&self) -> Option<(Complex<i64>, Complex<i64>)> {
+        let first = self.points.first()?.position;
+
+        Some(
+            self.points
+                .iter()
+                .map(|it| it.position)
+                .fold((first, first), expand),
+        )
+    }
+
+    pub fn max_distance(&self) -> i64 {
+        self.points
+            .iter()
+            .map(|it| it.position.l1_norm())
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn total_distance(&self) -> i64 {
+        self.points
+            .windows(2)
+            .map(|it| (it[1].position - it[0].position).l1_norm())
+            .sum()
+    }
+
+    pub fn to_svg(&self) -> String {
+        let Some((low, high)) = self.bounding_box() else {
+            return String::new();
+        };
+
+        // SVG's y axis points south
+        let polyline = |points: &[Complex<i64>], style: &str| {
+            let points = points
+                .iter()
+                .map(|it| format!("{},{}", it.re, -it.im))
+                .join(" ");
+            format!("<polyline points=\"{points}\" fill=\"none\" {style}/>")
+        };
+
+        let positions = self.points.iter().map(|it| it.position).collect_vec();
+        let waypoints = self
+            .points
+            .iter()
+            .filter_map(|it| it.waypoint)
+            .collect_vec();
+
+        let mut lines = vec![polyline(&positions, "stroke=\"black\"")];
+        if !waypoints.is_empty() {
+            lines.push(polyline(
+                &waypoints,
+                "stroke=\"red\" stroke-dasharray=\"4\"",
+            ));
+        }
+
+        let (low, high) = waypoints.iter().copied().fold((low, high), expand);
+
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">\n{}\n</svg>",
+            low.re - 1,
+            -high.im - 1,
+            high.re - low.re + 2,
+            high.im - low.im + 2,
+            lines.join("\n")
+        )
+    }
+}
+
+fn expand(
+    (low, high): (Complex<i64>, Complex<i64>),
+    pos: Complex<i64>,
+) -> (Complex<i64>, Complex<i64>) {
+    (
+        Complex::new(low.re.min(pos.re), low.im.min(pos.im)),
+        Complex::new(high.re.max(pos.re), high.im.max(pos.im)),
+    )
+}
+
+impl TrajectoryPoint {
+    fn of<T: Ship>(ship: &T) -> Self {
+        Self {
+            position: ship.position(),
+            waypoint: ship.waypoint(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quarter_turns() {
+        let mut ship = Simple::new();
+        for action in &[
+            NavigationAction::Right(450),
+            NavigationAction::Forward(3),
+            NavigationAction::Left(-90),
+            NavigationAction::Forward(2),
+        ] {
+            assert_eq!(Ok(()), ship.perform_action(action));
+        }
+        assert_eq!(5, ship.get_distance());
+
+        let mut ship = WithWaypoint::new();
+        assert_eq!(Ok(()), ship.perform_action(&NavigationAction::Left(720)));
+        assert_eq!(Ok(()), ship.perform_action(&NavigationAction::Forward(1)));
+        assert_eq!(11, ship.get_distance());
+    }
+
+    #[test]
+    fn test_unsupported_angles() {
+        let action = NavigationAction::Right(45);
+        let err = Err(NavigationError::UnsupportedTurn(action));
+        assert_eq!(err, Simple::new().perform_action(&action));
+        assert_eq!(err, WithWaypoint::new().perform_action(&action));
+        assert_eq!(
+            "Cannot perform R45, only multiples of 90 degrees are supported",
+            NavigationError::UnsupportedTurn(action).to_string()
+        );
+    }
+
+    #[test]
+    fn test_free_rotation() {
+        let mut ship = FreeRotation::new(Steering::Heading);
+        for action in &[
+            NavigationAction::Left(60),
+            NavigationAction::Forward(10),
+            NavigationAction::Right(150),
+            NavigationAction::Forward(10),
+        ] {
+            assert_eq!(Ok(()), ship.perform_action(action));
+        }
+        // 5 east and 8.66 north, then 10 south
+        assert_eq!(6, ship.get_distance());
+
+        let mut ship = FreeRotation::new(Steering::Waypoint);
+        for action in &[
+            NavigationAction::Forward(10),
+            NavigationAction::North(3),
+            NavigationAction::Forward(7),
+            NavigationAction::Right(90),
+            NavigationAction::Forward(11),
+        ] {
+            assert_eq!(Ok(()), ship.perform_action(action));
+        }
+        assert_eq!(286, ship.get_distance());
+    }
+
+    #[test]
+    fn test_trajectory() {
+        let actions = [
+            NavigationAction::Forward(10),
+            NavigationAction::North(3),
+            NavigationAction::Forward(7),
+            NavigationAction::Right(90),
+            NavigationAction::Forward(11),
+        ];
+
+        let Ok(trajectory) = Trajectory::record(&mut Simple::new(), &actions) else {
+            panic!("Could not record trajectory")
+        };
+        assert_eq!(6, trajectory.points().len());
+        assert_eq!(
+            Some((Complex::new(0, -8), Complex::new(17, 3))),
+            trajectory.bounding_box()
+        );
+        assert_eq!(25, trajectory.max_distance());
+        assert_eq!(31, trajectory.total_distance());
+
+        let svg = trajectory.to_svg();
+        assert!(svg.contains("points=\"0,0 10,0 10,-3 17,-3 17,-3 17,8\""));
+        assert!(!svg.contains("stroke=\"red\""));
+
+        let Ok(trajectory) = Trajectory::record(&mut WithWaypoint::new(), &actions) else {
+            panic!("Could not record trajectory")
+        };
+        assert_eq!(Some(Complex::new(10, 1)), trajectory.points()[0].waypoint);
+        assert_eq!(286, trajectory.max_distance());
+        assert!(trajectory.to_svg().contains("stroke=\"red\""));
+
+        let turn = NavigationAction::Left(10);
+        assert_eq!(
+            Err(NavigationError::UnsupportedTurn(turn)),
+            Trajectory::record(&mut Simple::new(), &[turn])
+        );
+    }
+
+    #[test]
+    fn test_state_and_reset() {
+        let mut ship = Simple::new();
+        assert_eq!(Some(0.0), ship.heading());
+        assert_eq!(Ok(()), ship.perform_action(&NavigationAction::Left(90)));
+        assert_eq!(Ok(()), ship.perform_action(&NavigationAction::Forward(4)));
+        assert_eq!(Some(90.0), ship.heading());
+        assert_eq!(Complex::new(0, 4), ship.position());
+        assert_eq!(None, ship.waypoint());
+
+        ship.reset();
+        assert_eq!(Complex::new(0, 0), ship.position());
+        assert_eq!(Some(0.0), ship.heading());
+
+        let mut ship = WithWaypoint::new();
+        assert_eq!(Ok(()), ship.perform_action(&NavigationAction::Forward(2)));
+        assert_eq!(Some(Complex::new(30, 3)), ship.waypoint());
+        assert_eq!(None, ship.heading());
+        ship.reset();
+        assert_eq!(Some(Complex::new(10, 1)), ship.waypoint());
+
+        let mut ship = FreeRotation::new(Steering::Heading);
+        assert_eq!(Ok(()), ship.perform_action(&NavigationAction::Right(45)));
+        assert_eq!(Some(315), ship.heading().map(|it| it.round() as i64));
+    }
+
+    #[test]
+    fn test_fuel_limited() {
+        let mut ship = FuelLimited::new(Simple::new(), 20).with_max_speed(15);
+
+        assert_eq!(Ok(()), ship.perform_action(&NavigationAction::Forward(10)));
+        assert_eq!(
+            Err(NavigationError::TooFast {
+                distance: 16,
+                limit: 15
+            }),
+            ship.perform_action(&NavigationAction::North(16))
+        );
+        assert_eq!(
+            Err(NavigationError::OutOfFuel {
+                needed: 11,
+                remaining: 10
+            }),
+            ship.perform_action(&NavigationAction::Forward(11))
+        );
+        assert_eq!(Ok(()), ship.perform_action(&NavigationAction::Right(90)));
+        assert_eq!(Ok(()), ship.perform_action(&NavigationAction::Forward(10)));
+        assert_eq!(Complex::new(10, -10), ship.position());
+        assert_eq!(0, ship.fuel());
+
+        ship.reset();
+        assert_eq!(20, ship.fuel());
+        assert_eq!(Complex::new(0, 0), ship.position());
+
+        // Moving the waypoint is free, only the ship burns fuel
+        let mut ship = FuelLimited::new(WithWaypoint::new(), 100);
+        assert_eq!(Ok(()), ship.perform_action(&NavigationAction::North(50)));
+        assert_eq!(100, ship.fuel());
+    }
+
+    #[test]
+    fn test_drifting() {
+        let actions = [
+            NavigationAction::Forward(10),
+            NavigationAction::North(3),
+            NavigationAction::Forward(7),
+        ];
+
+        let mut ship = Drifting::new(Simple::new(), Complex::new(0, -1));
+        for action in &actions {
+            assert_eq!(Ok(()), ship.perform_action(action));
+        }
+        assert_eq!(Complex::new(17, -17), ship.position());
+        assert_eq!(34, ship.get_distance());
+
+        let mut ship = Drifting::new(WithWaypoint::new(), Complex::new(1, 0));
+        for action in &actions {
+            assert_eq!(Ok(()), ship.perform_action(action));
+        }
+        // The ship covers 110 and then 98 units
+        assert_eq!(Complex::new(170 + 208, 38), ship.position());
+        assert_eq!(Some(Complex::new(180 + 208, 42)), ship.waypoint());
+    }
 }